@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::ron_asset::RonAsset;
+use crate::{ENEMY_CONTACT_DAMAGE, ENEMY_JUMP_STRENGTH, ENEMY_SIZE, ENEMY_SPEED};
+
+/// Which frames of a zombie's sprite sheet to show. Ranges are inclusive.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct ZombieFrames {
+    pub run: (usize, usize),
+    /// Shown while jumping or falling.
+    pub jump: (usize, usize),
+    /// Shown when the zombie is killed.
+    pub hurt: usize,
+    /// Shown for the second half of its death, once it has collapsed.
+    pub fallen: usize,
+}
+
+impl Default for ZombieFrames {
+    fn default() -> Self {
+        Self {
+            run: (9, 10),
+            jump: (13, 14),
+            hurt: 4,
+            fallen: 3,
+        }
+    }
+}
+
+/// A kind of zombie. Fields left out of the asset fall back to the plain
+/// walker's.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ZombieArchetype {
+    pub name: String,
+    /// Relative chance of being picked, against the other archetypes.
+    pub weight: f32,
+    /// Chasing speed, before the difficulty curve's `zombie_speed_scale`.
+    pub speed: f32,
+    pub jump_strength: f32,
+    /// Collision box. The sprite is scaled with its height.
+    pub size: Vec2,
+    pub health: f32,
+    /// Taken from the player on contact.
+    pub damage: f32,
+    /// Asset path of the sprite sheet.
+    pub sprite_sheet: String,
+    /// Size of one frame of `sprite_sheet`, in pixels.
+    pub tile_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub frames: ZombieFrames,
+}
+
+impl Default for ZombieArchetype {
+    fn default() -> Self {
+        Self {
+            name: "walker".into(),
+            weight: 1.0,
+            speed: ENEMY_SPEED,
+            jump_strength: ENEMY_JUMP_STRENGTH,
+            size: ENEMY_SIZE,
+            health: 3.0,
+            damage: ENEMY_CONTACT_DAMAGE,
+            sprite_sheet: "zombie_tilesheet.png".into(),
+            tile_size: Vec2::new(80.0, 110.0),
+            columns: 9,
+            rows: 3,
+            frames: ZombieFrames::default(),
+        }
+    }
+}
+
+/// Every kind of zombie, loaded from `assets/zombies.archetypes.ron`.
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct ZombieArchetypes {
+    pub archetypes: Vec<ZombieArchetype>,
+}
+
+impl Default for ZombieArchetypes {
+    fn default() -> Self {
+        Self {
+            archetypes: vec![ZombieArchetype::default()],
+        }
+    }
+}
+
+impl ZombieArchetypes {
+    /// Picks an archetype at random, in proportion to the weights. Negative
+    /// weights count as zero; if nothing has any weight the first is used.
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<&ZombieArchetype> {
+        let total: f32 = self.archetypes.iter().map(|a| a.weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return self.archetypes.first();
+        }
+
+        let mut roll = rng.gen_range(0.0..total);
+        for archetype in &self.archetypes {
+            let weight = archetype.weight.max(0.0);
+            if roll < weight {
+                return Some(archetype);
+            }
+            roll -= weight;
+        }
+        // Only reachable through rounding, so settle on the last with any weight
+        self.archetypes.iter().rev().find(|a| a.weight > 0.0)
+    }
+}
+
+impl RonAsset for ZombieArchetypes {
+    const EXTENSIONS: &'static [&'static str] = &["archetypes.ron"];
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::ron_asset::parse;
+
+    fn archetype(name: &str, weight: f32) -> ZombieArchetype {
+        ZombieArchetype {
+            name: name.into(),
+            weight,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn picks_in_proportion_to_weight() {
+        let archetypes = ZombieArchetypes {
+            archetypes: vec![
+                archetype("common", 3.0),
+                archetype("never", 0.0),
+                archetype("rare", 1.0),
+            ],
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut common = 0;
+        for _ in 0..1000 {
+            let picked = archetypes.pick(&mut rng).unwrap();
+            assert_ne!(picked.name, "never");
+            if picked.name == "common" {
+                common += 1;
+            }
+        }
+        assert!((650..850).contains(&common), "picked common {common} times");
+    }
+
+    #[test]
+    fn falls_back_to_the_first_without_weights() {
+        let archetypes = ZombieArchetypes {
+            archetypes: vec![archetype("first", 0.0), archetype("second", -1.0)],
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(archetypes.pick(&mut rng).unwrap().name, "first");
+        assert!(ZombieArchetypes { archetypes: vec![] }
+            .pick(&mut rng)
+            .is_none());
+    }
+
+    #[test]
+    fn missing_fields_are_the_walkers() {
+        let archetypes: ZombieArchetypes =
+            ron::from_str("(archetypes: [(name: \"runner\", speed: 400.0)])").unwrap();
+        let runner = &archetypes.archetypes[0];
+        assert_eq!(runner.speed, 400.0);
+        assert_eq!(runner.health, ZombieArchetype::default().health);
+        assert_eq!(runner.frames, ZombieFrames::default());
+    }
+
+    #[test]
+    fn bundled_archetypes_parse() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/zombies.archetypes.ron");
+        let archetypes: ZombieArchetypes =
+            parse(path.as_ref(), &std::fs::read(path).unwrap()).unwrap();
+        assert!(!archetypes.archetypes.is_empty());
+    }
+}
@@ -62,7 +62,7 @@ pub fn initialize_camera_system(
                 ..default()
             },
         ],
-        camera: camera,
+        camera,
     };
     create_parallax.send(event);
 }
@@ -77,13 +77,13 @@ pub fn move_camera_system(
         move_event_writer.send(ParallaxMoveEvent {
             translation: Vec2::new(3.0, 0.0),
             rotation: 0.,
-            camera: camera,
+            camera,
         });
     } else if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft) {
         move_event_writer.send(ParallaxMoveEvent {
             translation: Vec2::new(-3.0, 0.0),
             rotation: 0.,
-            camera: camera,
+            camera,
         });
     }
 }
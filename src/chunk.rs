@@ -0,0 +1,173 @@
+use bevy::asset::LoadedFolder;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ron_asset::RonAsset;
+use crate::{Crumble, PlatformMotion};
+
+/// A platform inside a chunk. `offset` is from the chunk origin to the
+/// platform's centre, and is where a moving platform starts its motion.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChunkPlatform {
+    pub offset: Vec2,
+    pub width: f32,
+    #[serde(default)]
+    pub motion: Option<PlatformMotion>,
+    #[serde(default)]
+    pub crumble: Option<Crumble>,
+    #[serde(default)]
+    pub one_way: bool,
+}
+
+/// A damaging area inside a chunk. `offset` is from the chunk origin to the
+/// hazard's centre.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChunkHazard {
+    pub offset: Vec2,
+    pub size: Vec2,
+    #[serde(default = "default_hazard_damage")]
+    pub damage_per_second: f32,
+}
+
+fn default_hazard_damage() -> f32 {
+    60.0
+}
+
+/// A hand-authored rooftop set-piece, loaded from `*.chunk.ron` or
+/// `*.chunk.json` files in `assets/chunks`.
+///
+/// The chunk is placed so that its origin lands where the left edge of the
+/// next generated platform would have been, so the first platform should sit
+/// at `offset.x == width / 2.0`, `offset.y == 0.0`.
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct LevelChunk {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub platforms: Vec<ChunkPlatform>,
+    #[serde(default)]
+    pub hazards: Vec<ChunkHazard>,
+    #[serde(default)]
+    pub spawns: Vec<Vec2>,
+}
+
+impl LevelChunk {
+    /// Whether the chunk carries every tag in `required`.
+    pub fn has_tags(&self, required: &[String]) -> bool {
+        required.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    /// The difficulty tier from a `tier:N` tag, or 0 if the chunk has none.
+    pub fn tier(&self) -> u32 {
+        self.tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix("tier:")?.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// Handle to the folder of chunks available to the generator.
+#[derive(Resource)]
+pub struct ChunkLibrary(pub Handle<LoadedFolder>);
+
+impl RonAsset for LevelChunk {
+    const EXTENSIONS: &'static [&'static str] = &["chunk.ron", "chunk.json"];
+
+    /// A platform can't both move and crumble, since falling away would
+    /// fight its motion.
+    fn validate(&self) -> Result<(), String> {
+        match self
+            .platforms
+            .iter()
+            .position(|platform| platform.motion.is_some() && platform.crumble.is_some())
+        {
+            Some(index) => Err(format!("platform {index} both moves and crumbles")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{PlatformGenerator, PlatformSpec};
+    use crate::ron_asset::{parse, RonAssetLoaderError};
+
+    #[test]
+    fn parses_ron_and_json() {
+        let ron_chunk: LevelChunk = ron::de::from_str(
+            r#"(
+                tags: ["tier:1"],
+                platforms: [(offset: (100.0, 0.0), width: 200.0)],
+                spawns: [(150.0, 60.0)],
+            )"#,
+        )
+        .unwrap();
+        let json_chunk: LevelChunk = serde_json::from_str(
+            r#"{
+                "tags": ["tier:1"],
+                "platforms": [{ "offset": [100.0, 0.0], "width": 200.0 }],
+                "spawns": [[150.0, 60.0]]
+            }"#,
+        )
+        .unwrap();
+
+        for chunk in [ron_chunk, json_chunk] {
+            assert_eq!(chunk.platforms[0].offset, Vec2::new(100.0, 0.0));
+            assert_eq!(chunk.spawns, vec![Vec2::new(150.0, 60.0)]);
+            assert!(chunk.hazards.is_empty());
+            assert!(chunk.has_tags(&["tier:1".to_string()]));
+            assert!(!chunk.has_tags(&["biome:neon".to_string()]));
+            assert_eq!(chunk.tier(), 1);
+        }
+    }
+
+    #[test]
+    fn rejects_platforms_that_move_and_crumble() {
+        let result = parse::<LevelChunk>(
+            "bad.chunk.ron".as_ref(),
+            br#"(platforms: [
+                (offset: (100.0, 0.0), width: 200.0),
+                (
+                    offset: (400.0, 0.0),
+                    width: 100.0,
+                    motion: Some((path: (0.0, 100.0), period: 2.0)),
+                    crumble: Some((delay: 0.5)),
+                ),
+            ])"#,
+        );
+        assert!(matches!(result, Err(RonAssetLoaderError::Invalid(_))));
+    }
+
+    #[test]
+    fn bundled_chunks_are_completable() {
+        let generator = PlatformGenerator::default();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/chunks");
+
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let chunk: LevelChunk = parse(&path, &std::fs::read(&path).unwrap()).unwrap();
+
+            let entry = &chunk.platforms[0];
+            assert_eq!(entry.offset.x, entry.width / 2.0, "{path:?} entry platform");
+            assert_eq!(entry.offset.y, 0.0, "{path:?} entry platform");
+
+            let mut platforms: Vec<_> = chunk
+                .platforms
+                .iter()
+                .map(|platform| PlatformSpec {
+                    position: platform.offset,
+                    width: platform.width,
+                })
+                .collect();
+            platforms.sort_by(|a, b| a.left_edge().total_cmp(&b.left_edge()));
+            for pair in platforms.windows(2) {
+                assert!(
+                    generator.is_reachable(&pair[0], &pair[1]),
+                    "{path:?}: {:?} -> {:?}",
+                    pair[0],
+                    pair[1]
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use fuzzy_runner::collision::Aabb;
+use fuzzy_runner::powerup::{ActivePowerUps, PowerUp};
+use fuzzy_runner::save::{SaveData, SAVE_PATH};
+use fuzzy_runner::{BodySize, Coin, Coins, GameState, PlatformMovement, Player};
+
+/// Coins closer than this to the player start flying towards them.
+const MAGNET_RADIUS: f32 = 120.0;
+const MAGNET_SPEED: f32 = 600.0;
+/// Multiplier on `MAGNET_RADIUS` while the magnet power-up is running.
+const MAGNET_POWER_UP_SCALE: f32 = 4.0;
+
+pub struct CoinPlugin;
+
+impl Plugin for CoinPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_save)
+            .add_systems(OnEnter(GameState::GameOver), bank_coins)
+            .add_systems(
+                FixedUpdate,
+                (attract_coins, collect_coins)
+                    .chain()
+                    .after(PlatformMovement)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn load_save(mut commands: Commands) {
+    let save = SaveData::load(SAVE_PATH).unwrap_or_else(|error| {
+        warn!("{error}, starting a fresh save");
+        SaveData::default()
+    });
+    commands.insert_resource(save);
+}
+
+/// Adds the coins from the run that just ended to the save and writes it out.
+pub fn bank_coins(coins: Res<Coins>, mut save: ResMut<SaveData>) {
+    save.banked_coins += coins.0 as u64;
+    if let Err(error) = save.store(SAVE_PATH) {
+        error!("{error}");
+    }
+}
+
+/// Pulls in coins that come within `MAGNET_RADIUS` of the player. Once a coin
+/// is attracted it keeps following them even if they outrun the radius.
+fn attract_coins(
+    player_query: Query<(&Transform, &ActivePowerUps), With<Player>>,
+    mut coin_query: Query<(&mut Transform, &mut Coin), Without<Player>>,
+    time: Res<Time>,
+) {
+    if let Ok((player_transform, power_ups)) = player_query.get_single() {
+        let target = player_transform.translation.truncate();
+        let radius = if power_ups.is_active(PowerUp::Magnet) {
+            MAGNET_RADIUS * MAGNET_POWER_UP_SCALE
+        } else {
+            MAGNET_RADIUS
+        };
+        for (mut transform, mut coin) in &mut coin_query {
+            let offset = target - transform.translation.truncate();
+            coin.attracted |= offset.length() < radius;
+            if coin.attracted {
+                let step = offset.clamp_length_max(MAGNET_SPEED * time.delta_seconds());
+                transform.translation += step.extend(0.0);
+            }
+        }
+    }
+}
+
+fn collect_coins(
+    mut commands: Commands,
+    player_query: Query<(&Transform, &BodySize), With<Player>>,
+    coin_query: Query<(Entity, &Transform), (With<Coin>, Without<Player>)>,
+    mut coins: ResMut<Coins>,
+) {
+    if let Ok((player_transform, size)) = player_query.get_single() {
+        let player_box = Aabb::new(player_transform.translation.truncate(), **size);
+        for (entity, transform) in &coin_query {
+            if player_box.overlaps(&Aabb::from_transform(transform)) {
+                coins.0 += 1;
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use bevy::prelude::*;
+
+use crate::{PlayerHit, GROUND_TOLERANCE, PLATFORM_THICKNESS};
+
+/// Furthest a body moves between collision checks, so it can't skip over a
+/// platform in one step.
+const MAX_STEP: f32 = PLATFORM_THICKNESS / 2.0;
+
+/// An axis-aligned box, by centre and half extents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub center: Vec2,
+    pub half_size: Vec2,
+}
+
+impl Aabb {
+    pub fn new(center: Vec2, size: Vec2) -> Self {
+        Self {
+            center,
+            half_size: size / 2.0,
+        }
+    }
+
+    /// Platforms, hazards and the like are sized by their transform's scale.
+    pub fn from_transform(transform: &Transform) -> Self {
+        Self::new(transform.translation.truncate(), transform.scale.truncate())
+    }
+
+    pub fn min(&self) -> Vec2 {
+        self.center - self.half_size
+    }
+
+    pub fn max(&self) -> Vec2 {
+        self.center + self.half_size
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        let (min, max) = (self.min(), self.max());
+        let (other_min, other_max) = (other.min(), other.max());
+        min.x < other_max.x && max.x > other_min.x && min.y < other_max.y && max.y > other_min.y
+    }
+}
+
+/// Whether `body` is coming down on top of `target` rather than running into
+/// it: moving down relative to it, with its feet above the target's middle.
+pub fn is_stomp(body: &Aabb, velocity: Vec2, target: &Aabb, target_velocity: Vec2) -> bool {
+    velocity.y < target_velocity.y && body.min().y > target.center.y
+}
+
+/// Works out the one hit the player takes from everything touching them this
+/// tick, however many zombies that is: the hardest hitter's, and none while
+/// bouncing off one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContactHits {
+    stomped: bool,
+    hardest: Option<PlayerHit>,
+}
+
+impl ContactHits {
+    /// The player landed on something this tick.
+    pub fn stomp(&mut self) {
+        self.stomped = true;
+    }
+
+    /// Something at `source` touched the player for `damage`.
+    pub fn touch(&mut self, source: Vec2, damage: f32) {
+        if self.hardest.is_none_or(|hit| damage > hit.damage) {
+            self.hardest = Some(PlayerHit { damage, source });
+        }
+    }
+
+    /// The hit to take, if any. A shielded player takes none.
+    pub fn hit(self, shielded: bool) -> Option<PlayerHit> {
+        self.hardest.filter(|_| !self.stomped && !shielded)
+    }
+}
+
+/// Which kind of surface a contact is with, from the body's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Surface {
+    Floor,
+    Wall,
+    Ceiling,
+}
+
+/// A surface a body was pushed out of. `normal` points away from the
+/// surface, towards the body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub entity: Entity,
+    pub normal: Vec2,
+}
+
+impl Contact {
+    pub fn surface(&self) -> Surface {
+        if self.normal.y > 0.0 {
+            Surface::Floor
+        } else if self.normal.y < 0.0 {
+            Surface::Ceiling
+        } else {
+            Surface::Wall
+        }
+    }
+}
+
+/// The contacts a body made while being resolved this frame.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Contacts(pub Vec<Contact>);
+
+impl Contacts {
+    /// The floor the body is standing on, if any.
+    pub fn ground(&self) -> Option<Entity> {
+        self.0
+            .iter()
+            .find(|contact| contact.surface() == Surface::Floor)
+            .map(|contact| contact.entity)
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.ground().is_some()
+    }
+
+    pub fn is_touching_wall(&self) -> bool {
+        self.wall_normal().is_some()
+    }
+
+    /// Horizontal direction pointing away from the wall being touched: `1.0`
+    /// for a wall on the body's left, `-1.0` for one on its right.
+    pub fn wall_normal(&self) -> Option<f32> {
+        self.0
+            .iter()
+            .find(|contact| contact.surface() == Surface::Wall)
+            .map(|contact| contact.normal.x)
+    }
+
+    pub fn is_touching_ceiling(&self) -> bool {
+        self.0
+            .iter()
+            .any(|contact| contact.surface() == Surface::Ceiling)
+    }
+}
+
+/// Something a body can collide with.
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub entity: Entity,
+    pub aabb: Aabb,
+    /// Only catches bodies falling onto its top.
+    pub one_way: bool,
+}
+
+/// What happened while resolving a body against the world.
+#[derive(Clone, Debug, Default)]
+pub struct Resolution {
+    pub contacts: Contacts,
+    /// Whether the body still overlaps the collider it was told to ignore.
+    pub overlapping_ignored: bool,
+}
+
+impl Resolution {
+    /// Stores the contacts and returns the floor the body is standing on.
+    /// The platform being dropped through is let go of once the body is
+    /// clear of it.
+    pub fn settle(
+        self,
+        contacts: &mut Contacts,
+        drop_through: &mut Option<Entity>,
+    ) -> Option<Entity> {
+        if !self.overlapping_ignored {
+            *drop_through = None;
+        }
+        *contacts = self.contacts;
+        contacts.ground()
+    }
+}
+
+/// Pushes `body` out of every collider it overlaps along the axis of least
+/// penetration, and stops `velocity` going into the surfaces it hits.
+///
+/// A body resting within `GROUND_TOLERANCE` above a top counts as standing on
+/// it. One-way colliders only catch bodies whose feet were above the top
+/// before this frame's movement. `ignore` is skipped, e.g. a one-way platform
+/// being dropped through.
+pub fn resolve(
+    body: &mut Aabb,
+    velocity: &mut Vec2,
+    delta_seconds: f32,
+    colliders: impl IntoIterator<Item = Collider>,
+    ignore: Option<Entity>,
+) -> Resolution {
+    let mut resolution = Resolution::default();
+    let fall_distance = (-velocity.y * delta_seconds).max(0.0);
+
+    for collider in colliders {
+        let (min, max) = (body.min(), body.max());
+        let (other_min, other_max) = (collider.aabb.min(), collider.aabb.max());
+
+        let overlap_x = max.x.min(other_max.x) - min.x.max(other_min.x);
+        let overlap_y = max.y.min(other_max.y) - min.y.max(other_min.y);
+        let floor_penetration = other_max.y - min.y;
+
+        if overlap_x <= 0.0 || overlap_y <= -GROUND_TOLERANCE {
+            continue;
+        }
+        if ignore == Some(collider.entity) {
+            resolution.overlapping_ignored = true;
+            continue;
+        }
+
+        let resting = velocity.y <= 0.0 && floor_penetration > -GROUND_TOLERANCE;
+        let landing = if collider.one_way {
+            resting && floor_penetration <= fall_distance + GROUND_TOLERANCE
+        } else {
+            resting && floor_penetration <= GROUND_TOLERANCE
+        };
+
+        let normal = if landing {
+            body.center.y += floor_penetration;
+            Vec2::Y
+        } else if collider.one_way || overlap_y <= 0.0 {
+            continue;
+        } else if overlap_y < overlap_x {
+            if body.center.y > collider.aabb.center.y {
+                body.center.y += overlap_y;
+                Vec2::Y
+            } else {
+                body.center.y -= overlap_y;
+                Vec2::NEG_Y
+            }
+        } else if body.center.x > collider.aabb.center.x {
+            body.center.x += overlap_x;
+            Vec2::X
+        } else {
+            body.center.x -= overlap_x;
+            Vec2::NEG_X
+        };
+
+        // Stop moving into the surface
+        if velocity.dot(normal) < 0.0 {
+            *velocity -= normal * velocity.dot(normal);
+        }
+        resolution.contacts.0.push(Contact {
+            entity: collider.entity,
+            normal,
+        });
+    }
+
+    resolution
+}
+
+/// Moves `body` by `velocity` over `delta_seconds`, resolving collisions
+/// along the way.
+///
+/// The movement is split into steps no longer than `MAX_STEP`, so a long
+/// frame or a fast fall still lands on thin platforms instead of tunnelling
+/// through them.
+pub fn move_and_collide(
+    body: &mut Aabb,
+    velocity: &mut Vec2,
+    delta_seconds: f32,
+    colliders: &[Collider],
+    ignore: Option<Entity>,
+) -> Resolution {
+    let distance = velocity.length() * delta_seconds;
+    let steps = (distance / MAX_STEP).ceil().max(1.0) as u32;
+    let step_seconds = delta_seconds / steps as f32;
+
+    let mut resolution = Resolution::default();
+    for _ in 0..steps {
+        body.center += *velocity * step_seconds;
+        let step = resolve(
+            body,
+            velocity,
+            step_seconds,
+            colliders.iter().copied(),
+            ignore,
+        );
+
+        resolution.overlapping_ignored |= step.overlapping_ignored;
+        for contact in step.contacts.0 {
+            if !resolution.contacts.0.contains(&contact) {
+                resolution.contacts.0.push(contact);
+            }
+        }
+    }
+    resolution
+}
+
+/// Moves a body of `size` at `transform` by `velocity` through `platforms`,
+/// given as a platform query yields them: entity, transform and whether it's
+/// one-way. What the body touched is stored in `contacts`, and the floor it
+/// ends up standing on is returned.
+///
+/// `drop_through` is ignored, and let go of once the body is clear of it.
+pub fn move_body<'a>(
+    transform: &mut Transform,
+    size: Vec2,
+    velocity: &mut Vec2,
+    delta_seconds: f32,
+    platforms: impl IntoIterator<Item = (Entity, &'a Transform, bool)>,
+    drop_through: &mut Option<Entity>,
+    contacts: &mut Contacts,
+) -> Option<Entity> {
+    let colliders: Vec<_> = platforms
+        .into_iter()
+        .map(|(entity, transform, one_way)| Collider {
+            entity,
+            aabb: Aabb::from_transform(transform),
+            one_way,
+        })
+        .collect();
+
+    let mut body = Aabb::new(transform.translation.truncate(), size);
+    let resolution = move_and_collide(
+        &mut body,
+        velocity,
+        delta_seconds,
+        &colliders,
+        *drop_through,
+    );
+    transform.translation.x = body.center.x;
+    transform.translation.y = body.center.y;
+
+    resolution.settle(contacts, drop_through)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn platform(index: u32, center: Vec2, size: Vec2) -> Collider {
+        Collider {
+            entity: Entity::from_raw(index),
+            aabb: Aabb::new(center, size),
+            one_way: false,
+        }
+    }
+
+    #[test]
+    fn lands_on_floor() {
+        let floor = platform(0, Vec2::ZERO, Vec2::new(200.0, 20.0));
+        let mut body = Aabb::new(Vec2::new(0.0, 48.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, -300.0);
+
+        let resolution = resolve(&mut body, &mut velocity, DT, [floor], None);
+
+        assert_eq!(resolution.contacts.ground(), Some(floor.entity));
+        assert_eq!(body.min().y, 10.0);
+        assert_eq!(velocity.y, 0.0);
+    }
+
+    #[test]
+    fn resting_on_top_stays_grounded() {
+        let floor = platform(0, Vec2::ZERO, Vec2::new(200.0, 20.0));
+        let mut body = Aabb::new(Vec2::new(0.0, 50.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(100.0, 0.0);
+
+        let resolution = resolve(&mut body, &mut velocity, DT, [floor], None);
+
+        assert!(resolution.contacts.is_grounded());
+        assert!(!resolution.contacts.is_touching_wall());
+        assert_eq!(velocity.x, 100.0);
+    }
+
+    #[test]
+    fn side_hit_is_a_wall() {
+        let wall = platform(0, Vec2::ZERO, Vec2::new(200.0, 200.0));
+        let mut body = Aabb::new(Vec2::new(-120.0, 0.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(300.0, -50.0);
+
+        let resolution = resolve(&mut body, &mut velocity, DT, [wall], None);
+
+        assert!(resolution.contacts.is_touching_wall());
+        assert_eq!(resolution.contacts.wall_normal(), Some(-1.0));
+        assert_eq!(body.max().x, -100.0);
+        assert_eq!(velocity.x, 0.0);
+        assert_eq!(velocity.y, -50.0);
+    }
+
+    #[test]
+    fn head_hits_ceiling() {
+        let ceiling = platform(0, Vec2::ZERO, Vec2::new(200.0, 20.0));
+        let mut body = Aabb::new(Vec2::new(0.0, -48.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, 400.0);
+
+        let resolution = resolve(&mut body, &mut velocity, DT, [ceiling], None);
+
+        assert!(resolution.contacts.is_touching_ceiling());
+        assert_eq!(body.max().y, -10.0);
+        assert_eq!(velocity.y, 0.0);
+    }
+
+    #[test]
+    fn one_way_lets_bodies_up_through() {
+        let one_way = Collider {
+            one_way: true,
+            ..platform(0, Vec2::ZERO, Vec2::new(200.0, 20.0))
+        };
+
+        let mut rising = Aabb::new(Vec2::new(0.0, -30.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, 400.0);
+        let resolution = resolve(&mut rising, &mut velocity, DT, [one_way], None);
+        assert!(resolution.contacts.0.is_empty());
+        assert_eq!(velocity.y, 400.0);
+
+        // Falling with the feet already well below the top doesn't snap up
+        let mut sinking = Aabb::new(Vec2::new(0.0, 20.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, -60.0);
+        let resolution = resolve(&mut sinking, &mut velocity, DT, [one_way], None);
+        assert!(resolution.contacts.0.is_empty());
+    }
+
+    #[test]
+    fn ignored_collider_reports_overlap() {
+        let floor = platform(0, Vec2::ZERO, Vec2::new(200.0, 20.0));
+        let mut body = Aabb::new(Vec2::new(0.0, 45.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, -100.0);
+
+        let resolution = resolve(&mut body, &mut velocity, DT, [floor], Some(floor.entity));
+
+        assert!(resolution.overlapping_ignored);
+        assert!(!resolution.contacts.is_grounded());
+    }
+
+    #[test]
+    fn long_frame_still_lands() {
+        let floor = platform(0, Vec2::ZERO, Vec2::new(200.0, PLATFORM_THICKNESS));
+        let mut body = Aabb::new(Vec2::new(0.0, 300.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, -3000.0);
+
+        // One step would put the body 1200px below the platform
+        let resolution = move_and_collide(&mut body, &mut velocity, 0.5, &[floor], None);
+
+        assert_eq!(resolution.contacts.ground(), Some(floor.entity));
+        assert!((body.min().y - PLATFORM_THICKNESS / 2.0).abs() < 1e-3);
+        assert_eq!(velocity.y, 0.0);
+    }
+
+    #[test]
+    fn long_frame_still_hits_walls() {
+        let wall = platform(0, Vec2::ZERO, Vec2::new(PLATFORM_THICKNESS, 200.0));
+        let mut body = Aabb::new(Vec2::new(-200.0, 0.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(2000.0, 0.0);
+
+        let resolution = move_and_collide(&mut body, &mut velocity, 0.25, &[wall], None);
+
+        assert!(resolution.contacts.is_touching_wall());
+        assert!(body.max().x <= -PLATFORM_THICKNESS / 2.0 + 1e-3);
+    }
+
+    #[test]
+    fn long_frame_on_one_way_still_lands() {
+        let one_way = Collider {
+            one_way: true,
+            ..platform(0, Vec2::ZERO, Vec2::new(200.0, PLATFORM_THICKNESS))
+        };
+        let mut body = Aabb::new(Vec2::new(0.0, 400.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, -4000.0);
+
+        let resolution = move_and_collide(&mut body, &mut velocity, 0.5, &[one_way], None);
+
+        assert!(resolution.contacts.is_grounded());
+    }
+
+    #[test]
+    fn settling_lets_go_once_clear() {
+        let floor = platform(0, Vec2::ZERO, Vec2::new(200.0, 20.0));
+        let mut contacts = Contacts::default();
+        let mut drop_through = Some(floor.entity);
+
+        let mut body = Aabb::new(Vec2::new(0.0, 45.0), Vec2::new(50.0, 80.0));
+        let mut velocity = Vec2::new(0.0, -100.0);
+        let resolution = resolve(&mut body, &mut velocity, DT, [floor], drop_through);
+        assert_eq!(resolution.settle(&mut contacts, &mut drop_through), None);
+        assert_eq!(drop_through, Some(floor.entity));
+
+        let mut body = Aabb::new(Vec2::new(0.0, -60.0), Vec2::new(50.0, 80.0));
+        let resolution = resolve(&mut body, &mut velocity, DT, [floor], drop_through);
+        resolution.settle(&mut contacts, &mut drop_through);
+        assert_eq!(drop_through, None);
+    }
+
+    #[test]
+    fn hardest_hitter_lands_the_hit() {
+        let mut hits = ContactHits::default();
+        assert_eq!(hits.hit(false), None);
+
+        hits.touch(Vec2::new(-10.0, 0.0), 15.0);
+        hits.touch(Vec2::new(10.0, 0.0), 30.0);
+        hits.touch(Vec2::new(20.0, 0.0), 20.0);
+        assert_eq!(
+            hits.hit(false),
+            Some(PlayerHit {
+                damage: 30.0,
+                source: Vec2::new(10.0, 0.0)
+            })
+        );
+        assert_eq!(hits.hit(true), None);
+
+        hits.stomp();
+        assert_eq!(hits.hit(false), None);
+    }
+
+    #[test]
+    fn landing_on_top_is_a_stomp() {
+        let zombie = Aabb::new(Vec2::ZERO, Vec2::new(50.0, 80.0));
+        let player = Aabb::new(Vec2::new(10.0, 75.0), Vec2::new(50.0, 80.0));
+        assert!(is_stomp(
+            &player,
+            Vec2::new(0.0, -400.0),
+            &zombie,
+            Vec2::ZERO
+        ));
+    }
+
+    #[test]
+    fn running_into_the_side_is_not_a_stomp() {
+        let zombie = Aabb::new(Vec2::ZERO, Vec2::new(50.0, 80.0));
+        let player = Aabb::new(Vec2::new(45.0, 0.0), Vec2::new(50.0, 80.0));
+        assert!(!is_stomp(
+            &player,
+            Vec2::new(-300.0, 0.0),
+            &zombie,
+            Vec2::ZERO
+        ));
+        // Even when falling past it
+        assert!(!is_stomp(
+            &player,
+            Vec2::new(0.0, -400.0),
+            &zombie,
+            Vec2::ZERO
+        ));
+    }
+
+    #[test]
+    fn stomp_needs_to_close_in_from_above() {
+        let zombie = Aabb::new(Vec2::ZERO, Vec2::new(50.0, 80.0));
+        let player = Aabb::new(Vec2::new(0.0, 75.0), Vec2::new(50.0, 80.0));
+        // Rising away from it, or falling slower than it is
+        assert!(!is_stomp(
+            &player,
+            Vec2::new(0.0, 200.0),
+            &zombie,
+            Vec2::ZERO
+        ));
+        assert!(!is_stomp(
+            &player,
+            Vec2::new(0.0, -100.0),
+            &zombie,
+            Vec2::new(0.0, -400.0)
+        ));
+    }
+}
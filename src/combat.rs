@@ -0,0 +1,363 @@
+use bevy::prelude::*;
+use fuzzy_runner::collision::Aabb;
+use fuzzy_runner::{
+    AmmoPickup, BodySize, Dying, Enemy, GameState, HealthPickup, Interpolated, MeleeHitbox,
+    OnGameScreen, Platform, PlatformMovement, Player, PlayerHit, PlayerInput, Projectile,
+    PLAYER_SIZE, VIEWPORT_WIDTH,
+};
+
+const ATTACK_SIZE: Vec2 = Vec2::new(50.0, 60.0);
+const ATTACK_SECONDS: f32 = 0.12;
+/// Counted from the start of the swing.
+const ATTACK_COOLDOWN_SECONDS: f32 = 0.35;
+const ATTACK_DAMAGE: f32 = 1.0;
+/// Speed a hit zombie is thrown away from the player, and upwards.
+const KNOCKBACK: Vec2 = Vec2::new(400.0, 250.0);
+const STAGGER_SECONDS: f32 = 0.3;
+const STOMP_BOUNCE_SPEED: f32 = 550.0;
+const DEATH_SECONDS: f32 = 0.8;
+const ATTACK_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+const PROJECTILE_SIZE: Vec2 = Vec2::new(14.0, 6.0);
+const PROJECTILE_SPEED: f32 = 900.0;
+const PROJECTILE_DAMAGE: f32 = 1.0;
+const PROJECTILE_KNOCKBACK: f32 = 150.0;
+const PROJECTILE_COLOR: Color = Color::rgb(1.0, 0.9, 0.3);
+const FIRE_COOLDOWN_SECONDS: f32 = 0.25;
+/// Speed the player is thrown away from whatever hit them, and upwards.
+const HIT_KNOCKBACK: Vec2 = Vec2::new(350.0, 300.0);
+/// How long left/right input is ignored after a hit, so the knockback carries.
+const HIT_STUN_SECONDS: f32 = 0.2;
+const INVULNERABLE_SECONDS: f32 = 1.0;
+/// Length of each visible or faded half of the flashing while invulnerable.
+const FLASH_SECONDS: f32 = 0.08;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerHit>()
+            .add_systems(
+                Update,
+                flash_while_invulnerable.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    start_attack,
+                    fire_projectile,
+                    follow_player,
+                    melee_hits,
+                    move_projectiles,
+                    collect_pickups,
+                    expire_hitboxes,
+                    apply_player_hits,
+                )
+                    .chain()
+                    .after(PlatformMovement)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Swings in the direction the player is facing when attack is pressed.
+fn start_attack(
+    mut commands: Commands,
+    mut input: ResMut<PlayerInput>,
+    mut player_query: Query<(&Transform, &mut Player, &Sprite)>,
+    time: Res<Time>,
+) {
+    if let Ok((player_transform, mut player, sprite)) = player_query.get_single_mut() {
+        player.attack_cooldown.tick(time.delta());
+        if !std::mem::take(&mut input.attack_pressed) || !player.attack_cooldown.finished() {
+            return;
+        }
+        player.attack_cooldown = Timer::from_seconds(ATTACK_COOLDOWN_SECONDS, TimerMode::Once);
+
+        let direction = if sprite.flip_x { -1.0 } else { 1.0 };
+        let offset = Vec2::new(direction * (PLAYER_SIZE.x + ATTACK_SIZE.x) / 2.0, 0.0);
+        let translation = (player_transform.translation.truncate() + offset).extend(2.0);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: ATTACK_COLOR,
+                    ..default()
+                },
+                transform: Transform {
+                    translation,
+                    scale: ATTACK_SIZE.extend(1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            MeleeHitbox {
+                lifetime: Timer::from_seconds(ATTACK_SECONDS, TimerMode::Once),
+                offset,
+                size: ATTACK_SIZE,
+                direction,
+                hit: Vec::new(),
+            },
+            Interpolated::new(translation),
+            OnGameScreen,
+        ));
+    }
+}
+
+/// Fires a bullet the way the player is facing when fire is pressed, if
+/// there's ammo left.
+fn fire_projectile(
+    mut commands: Commands,
+    mut input: ResMut<PlayerInput>,
+    mut player_query: Query<(&Transform, &mut Player, &Sprite)>,
+    time: Res<Time>,
+) {
+    if let Ok((player_transform, mut player, sprite)) = player_query.get_single_mut() {
+        player.fire_cooldown.tick(time.delta());
+        if !std::mem::take(&mut input.fire_pressed)
+            || !player.fire_cooldown.finished()
+            || player.ammo == 0
+        {
+            return;
+        }
+        player.ammo -= 1;
+        player.fire_cooldown = Timer::from_seconds(FIRE_COOLDOWN_SECONDS, TimerMode::Once);
+
+        let direction = if sprite.flip_x { -1.0 } else { 1.0 };
+        let muzzle = Vec2::new(direction * PLAYER_SIZE.x / 2.0, 0.0);
+        let translation = (player_transform.translation.truncate() + muzzle).extend(2.0);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: PROJECTILE_COLOR,
+                    ..default()
+                },
+                transform: Transform {
+                    translation,
+                    scale: PROJECTILE_SIZE.extend(1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            Projectile {
+                velocity: Vec2::new(direction * PROJECTILE_SPEED, 0.0),
+            },
+            Interpolated::new(translation),
+            OnGameScreen,
+        ));
+    }
+}
+
+/// Keeps attack boxes in front of the player as they move.
+fn follow_player(
+    player_query: Query<&Transform, With<Player>>,
+    mut hitbox_query: Query<(&mut Transform, &MeleeHitbox), Without<Player>>,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        for (mut transform, hitbox) in &mut hitbox_query {
+            let position = player_transform.translation.truncate() + hitbox.offset;
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+}
+
+/// Damages and knocks back every zombie an attack box touches, killing the
+/// ones that run out of health.
+fn melee_hits(
+    mut commands: Commands,
+    mut hitbox_query: Query<(&Transform, &mut MeleeHitbox)>,
+    mut zombie_query: Query<(Entity, &Transform, &BodySize, &mut Enemy), Without<Dying>>,
+) {
+    for (hitbox_transform, mut hitbox) in &mut hitbox_query {
+        let hitbox_box = Aabb::new(hitbox_transform.translation.truncate(), hitbox.size);
+        for (entity, zombie_transform, size, mut zombie) in &mut zombie_query {
+            let zombie_box = Aabb::new(zombie_transform.translation.truncate(), **size);
+            if hitbox.hit.contains(&entity) || !hitbox_box.overlaps(&zombie_box) {
+                continue;
+            }
+            hitbox.hit.push(entity);
+
+            let knockback = Vec2::new(hitbox.direction * KNOCKBACK.x, KNOCKBACK.y);
+            hit_zombie(&mut commands, entity, &mut zombie, ATTACK_DAMAGE, knockback);
+        }
+    }
+}
+
+/// Damages a zombie and throws it back with `knockback`, staggering it, or
+/// starts its death animation if that was the last of its health.
+pub fn hit_zombie(
+    commands: &mut Commands,
+    entity: Entity,
+    zombie: &mut Enemy,
+    damage: f32,
+    knockback: Vec2,
+) {
+    zombie.health -= damage;
+    zombie.velocity = knockback;
+    if knockback.y > 0.0 {
+        zombie.is_grounded = false;
+        zombie.ground = None;
+    }
+    if zombie.health <= 0.0 {
+        // Stay limp until despawned
+        zombie.stagger = Timer::from_seconds(DEATH_SECONDS, TimerMode::Once);
+        commands
+            .entity(entity)
+            .insert(Dying(Timer::from_seconds(DEATH_SECONDS, TimerMode::Once)));
+    } else {
+        zombie.stagger = Timer::from_seconds(STAGGER_SECONDS, TimerMode::Once);
+    }
+}
+
+/// Kills a zombie the player landed on and bounces the player back up,
+/// with their air jumps restored.
+pub fn stomp_zombie(
+    commands: &mut Commands,
+    entity: Entity,
+    zombie: &mut Enemy,
+    player: &mut Player,
+) {
+    let health = zombie.health;
+    hit_zombie(commands, entity, zombie, health, Vec2::ZERO);
+    player.velocity.y = STOMP_BOUNCE_SPEED;
+    player.is_grounded = false;
+    player.ground = None;
+    player.air_jumps_used = 0;
+    player.jumping = false;
+}
+
+/// Moves bullets, stopping them at the first zombie or platform in the way.
+/// Each tick's whole path is checked so fast bullets can't skip past things.
+fn move_projectiles(
+    mut commands: Commands,
+    mut projectile_query: Query<(Entity, &mut Transform, &Projectile)>,
+    mut zombie_query: Query<
+        (Entity, &Transform, &BodySize, &mut Enemy),
+        (Without<Dying>, Without<Projectile>),
+    >,
+    platform_query: Query<&Transform, (With<Platform>, Without<Projectile>)>,
+    player_query: Query<&Transform, (With<Player>, Without<Projectile>)>,
+    time: Res<Time>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    for (entity, mut transform, projectile) in &mut projectile_query {
+        let start = transform.translation.truncate();
+        let step = projectile.velocity * time.delta_seconds();
+        transform.translation += step.extend(0.0);
+        let path = Aabb::new(start + step / 2.0, PROJECTILE_SIZE + step.abs());
+
+        let target = zombie_query
+            .iter_mut()
+            .find(|(_, zombie_transform, size, _)| {
+                path.overlaps(&Aabb::new(zombie_transform.translation.truncate(), ***size))
+            });
+        if let Some((zombie_entity, _, _, mut zombie)) = target {
+            let knockback = Vec2::new(projectile.velocity.x.signum() * PROJECTILE_KNOCKBACK, 0.0);
+            hit_zombie(
+                &mut commands,
+                zombie_entity,
+                &mut zombie,
+                PROJECTILE_DAMAGE,
+                knockback,
+            );
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let blocked = platform_query
+            .iter()
+            .any(|platform| path.overlaps(&Aabb::from_transform(platform)));
+        let offscreen = (transform.translation.x - player_transform.translation.x).abs()
+            > VIEWPORT_WIDTH / 2.0 + PROJECTILE_SIZE.x;
+        if blocked || offscreen {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &BodySize, &mut Player)>,
+    ammo_query: Query<(Entity, &Transform, &AmmoPickup), Without<Player>>,
+    health_query: Query<(Entity, &Transform, &HealthPickup), Without<Player>>,
+) {
+    if let Ok((player_transform, size, mut player)) = player_query.get_single_mut() {
+        let player_box = Aabb::new(player_transform.translation.truncate(), **size);
+        for (entity, transform, pickup) in &ammo_query {
+            if player_box.overlaps(&Aabb::from_transform(transform)) {
+                player.ammo += pickup.amount;
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for (entity, transform, pickup) in &health_query {
+            if player_box.overlaps(&Aabb::from_transform(transform)) {
+                match *pickup {
+                    HealthPickup::MedKit(amount) => player.heal(amount),
+                    HealthPickup::Upgrade(amount) => {
+                        player.max_health += amount;
+                        player.heal(amount);
+                    }
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn expire_hitboxes(
+    mut commands: Commands,
+    mut hitbox_query: Query<(Entity, &mut MeleeHitbox)>,
+    time: Res<Time>,
+) {
+    for (entity, mut hitbox) in &mut hitbox_query {
+        hitbox.lifetime.tick(time.delta());
+        if hitbox.lifetime.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Takes the damage from each hit on the player, knocks them away from its
+/// source and makes them invulnerable for a moment. Hits landing while the
+/// player is still invulnerable are ignored.
+fn apply_player_hits(
+    mut hits: EventReader<PlayerHit>,
+    mut player_query: Query<(&Transform, &mut Player)>,
+    time: Res<Time>,
+) {
+    let Ok((player_transform, mut player)) = player_query.get_single_mut() else {
+        hits.clear();
+        return;
+    };
+    player.invulnerable.tick(time.delta());
+
+    for hit in hits.read() {
+        if !player.invulnerable.finished() {
+            continue;
+        }
+        player.health -= hit.damage;
+
+        let away = if player_transform.translation.x < hit.source.x {
+            -1.0
+        } else {
+            1.0
+        };
+        player.velocity = Vec2::new(away * HIT_KNOCKBACK.x, HIT_KNOCKBACK.y);
+        player.is_grounded = false;
+        player.ground = None;
+        player.jumping = false;
+        // Knocked off the ground, not walked off it, so no coyote jump
+        player.jump_grace.consume();
+        player.input_lock = Timer::from_seconds(HIT_STUN_SECONDS, TimerMode::Once);
+        player.invulnerable = Timer::from_seconds(INVULNERABLE_SECONDS, TimerMode::Once);
+    }
+}
+
+fn flash_while_invulnerable(mut player_query: Query<(&Player, &mut Sprite)>) {
+    if let Ok((player, mut sprite)) = player_query.get_single_mut() {
+        let faded = !player.invulnerable.finished()
+            && ((player.invulnerable.elapsed_secs() / FLASH_SECONDS) as u32).is_multiple_of(2);
+        sprite.color.set_a(if faded { 0.3 } else { 1.0 });
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ron_asset::RonAsset;
+
+/// How hard the run is at a given point.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Difficulty {
+    /// Multiplier on the generator's gap range. Gaps are still capped at what
+    /// the player can reach.
+    pub gap_scale: f32,
+    /// Multiplier on the generator's platform width range.
+    pub width_scale: f32,
+    /// Added on top of `GameConfig::max_enemies`.
+    pub extra_enemies: f32,
+    /// Multiplier on each zombie's archetype speed.
+    pub zombie_speed_scale: f32,
+    /// Chunks tagged with a higher `tier:N` are left out.
+    pub max_chunk_tier: u32,
+    /// Chance that a generated platform crumbles.
+    pub crumbling_chance: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            gap_scale: 1.0,
+            width_scale: 1.0,
+            extra_enemies: 0.0,
+            zombie_speed_scale: 1.0,
+            max_chunk_tier: 1,
+            crumbling_chance: 0.0,
+        }
+    }
+}
+
+impl Difficulty {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            gap_scale: lerp(self.gap_scale, other.gap_scale),
+            width_scale: lerp(self.width_scale, other.width_scale),
+            extra_enemies: lerp(self.extra_enemies, other.extra_enemies),
+            zombie_speed_scale: lerp(self.zombie_speed_scale, other.zombie_speed_scale),
+            // Tiers step rather than blend
+            max_chunk_tier: self.max_chunk_tier,
+            crumbling_chance: lerp(self.crumbling_chance, other.crumbling_chance),
+        }
+    }
+
+    /// The enemy cap for this difficulty given the player's chosen base cap.
+    pub fn max_enemies(&self, base: u32) -> u32 {
+        base + self.extra_enemies.max(0.0) as u32
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DifficultyKey {
+    /// Distance into the run, in metres as shown in the HUD.
+    pub distance: f32,
+    pub difficulty: Difficulty,
+}
+
+/// Difficulty keyed on distance, loaded from `assets/difficulty.curve.ron`.
+///
+/// Values are interpolated linearly between keys and held flat before the
+/// first and after the last.
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct DifficultyCurve {
+    pub keys: Vec<DifficultyKey>,
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        Self {
+            keys: vec![DifficultyKey {
+                distance: 0.0,
+                difficulty: Difficulty::default(),
+            }],
+        }
+    }
+}
+
+impl DifficultyCurve {
+    pub fn sample(&self, distance: f32) -> Difficulty {
+        let Some(first) = self.keys.first() else {
+            return Difficulty::default();
+        };
+        if distance <= first.distance {
+            return first.difficulty;
+        }
+
+        for pair in self.keys.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            if distance < to.distance {
+                let t = (distance - from.distance) / (to.distance - from.distance);
+                return from.difficulty.lerp(&to.difficulty, t);
+            }
+        }
+        self.keys[self.keys.len() - 1].difficulty
+    }
+}
+
+impl RonAsset for DifficultyCurve {
+    const EXTENSIONS: &'static [&'static str] = &["curve.ron"];
+
+    fn finish(&mut self) {
+        self.keys.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ron_asset::parse;
+
+    fn curve() -> DifficultyCurve {
+        DifficultyCurve {
+            keys: vec![
+                DifficultyKey {
+                    distance: 100.0,
+                    difficulty: Difficulty::default(),
+                },
+                DifficultyKey {
+                    distance: 200.0,
+                    difficulty: Difficulty {
+                        gap_scale: 2.0,
+                        extra_enemies: 4.0,
+                        max_chunk_tier: 2,
+                        ..default()
+                    },
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn holds_flat_outside_keys() {
+        let curve = curve();
+        assert_eq!(curve.sample(0.0), Difficulty::default());
+        assert_eq!(curve.sample(500.0).gap_scale, 2.0);
+        assert_eq!(curve.sample(500.0).max_chunk_tier, 2);
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        let halfway = curve().sample(150.0);
+        assert!((halfway.gap_scale - 1.5).abs() < 1e-5);
+        assert_eq!(halfway.max_enemies(3), 5);
+        assert_eq!(halfway.max_chunk_tier, 1);
+    }
+
+    #[test]
+    fn bundled_curve_parses() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/difficulty.curve.ron");
+        let curve: DifficultyCurve = parse(path.as_ref(), &std::fs::read(path).unwrap()).unwrap();
+        assert!(!curve.keys.is_empty());
+    }
+
+    #[test]
+    fn loading_sorts_the_keys() {
+        let curve: DifficultyCurve = parse(
+            "test.curve.ron".as_ref(),
+            b"(keys: [(distance: 200.0, difficulty: ()), (distance: 100.0, difficulty: ())])",
+        )
+        .unwrap();
+        assert_eq!(curve.keys[0].distance, 100.0);
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use fuzzy_runner::collision::Aabb;
+use fuzzy_runner::powerup::{ActivePowerUps, PowerUp};
+use fuzzy_runner::{BodySize, GameState, PlatformMovement, Player};
+
+/// Game speed while slow-mo is running.
+const SLOW_MO_SPEED: f32 = 0.5;
+
+/// Collects power-up pickups, runs their timers and applies slow-mo. The
+/// other effects are checked where they apply, e.g. the speed boost in the
+/// player's `apply_forces`.
+pub struct EffectPlugin;
+
+impl Plugin for EffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::Playing), reset_game_speed)
+            .add_systems(
+                Update,
+                (tick_power_ups, apply_slow_mo)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                collect_power_ups
+                    .after(PlatformMovement)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn collect_power_ups(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &BodySize, &mut ActivePowerUps), With<Player>>,
+    pickup_query: Query<(Entity, &Transform, &PowerUp), Without<Player>>,
+) {
+    if let Ok((player_transform, size, mut power_ups)) = player_query.get_single_mut() {
+        let player_box = Aabb::new(player_transform.translation.truncate(), **size);
+        for (entity, transform, &power_up) in &pickup_query {
+            if player_box.overlaps(&Aabb::from_transform(transform)) {
+                power_ups.activate(power_up);
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Power-ups run on real time, so slow-mo doesn't stretch itself or the
+/// others.
+fn tick_power_ups(mut player_query: Query<&mut ActivePowerUps>, time: Res<Time<Real>>) {
+    if let Ok(mut power_ups) = player_query.get_single_mut() {
+        power_ups.tick(time.delta());
+    }
+}
+
+fn apply_slow_mo(player_query: Query<&ActivePowerUps>, mut time: ResMut<Time<Virtual>>) {
+    let slowed = player_query
+        .get_single()
+        .is_ok_and(|power_ups| power_ups.is_active(PowerUp::SlowMo));
+    let speed = if slowed { SLOW_MO_SPEED } else { 1.0 };
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+/// Menus and the game over screen always run at full speed.
+fn reset_game_speed(mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(1.0);
+}
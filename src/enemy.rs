@@ -1,73 +1,156 @@
 use bevy::prelude::*;
-use fuzzy_runner::{AnimationIndices, AnimationTimer, Enemy, GameConfig, GameState, OnGameScreen, Platform, Player, ENEMY_JUMP_STRENGTH, ENEMY_SIZE, ENEMY_SPEED, GRAVITY, PLAYER_SIZE};
+use fuzzy_runner::archetype::{ZombieArchetype, ZombieArchetypes};
+#[cfg(not(feature = "rapier"))]
+use fuzzy_runner::collision::{self, ContactHits};
+#[cfg(not(feature = "rapier"))]
+use fuzzy_runner::powerup::{ActivePowerUps, PowerUp};
+#[cfg(not(feature = "rapier"))]
+use fuzzy_runner::PlayerHit;
+use fuzzy_runner::collision::{Aabb, Contacts};
+use fuzzy_runner::{finished_timer, AnimationIndices, AnimationTimer, BodySize, Distance, Dying, Enemy, GameConfig, GameState, Interpolated, MovingPlatform, OnGameScreen, OneWay, Platform, PlatformMovement, Player, RunRng, ZombieSpawnPoint, ENEMY_SIZE, GRAVITY, PLAYER_SIZE, VIEWPORT_WIDTH};
+use fuzzy_runner::difficulty::{Difficulty, DifficultyCurve};
+use fuzzy_runner::ron_asset::{sync_asset_resource, ResourceHandle, RonAssetLoader};
+use rand::Rng;
+
+#[cfg(not(feature = "rapier"))]
+use crate::combat::stomp_zombie;
+#[cfg(feature = "rapier")]
+use crate::rapier::{zombie_platform_collision, zombie_player_collision};
+
+/// Horizontal damping while staggered, so knockback dies away.
+const STAGGER_DAMPING: f32 = 0.9;
+/// Sprite scale of a zombie `ENEMY_SIZE` tall, the same as the player's.
+const ZOMBIE_SPRITE_SCALE: f32 = 0.7;
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                manage_zombie_population,
-                animate_zombie,
-                zombie_movement_ai,
-                apply_velocity.before(zombie_platform_collision),
-                zombie_platform_collision,
-                zombie_player_collision.after(zombie_platform_collision),
+        app.init_asset::<ZombieArchetypes>()
+            .init_asset_loader::<RonAssetLoader<ZombieArchetypes>>()
+            .add_systems(Startup, load_zombie_archetypes)
+            .add_systems(
+                Update,
+                (
+                    sync_asset_resource::<ZombieArchetypes>,
+                    (
+                        manage_zombie_population,
+                        spawn_from_spawn_points,
+                        animate_zombie,
+                        play_zombie_death,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Playing)),
+                )
+                    .chain(),
             )
-                .chain()
-                .run_if(in_state(GameState::Playing)),
-        );
+            .add_systems(
+                FixedUpdate,
+                (
+                    zombie_movement_ai,
+                    ride_platforms,
+                    zombie_platform_collision,
+                    zombie_player_collision,
+                )
+                    .chain()
+                    .after(PlatformMovement)
+                    .run_if(in_state(GameState::Playing)),
+            );
     }
 }
 
+/// The difficulty at the player's current distance, flat until the curve has loaded.
+fn current_difficulty(curve: Option<&DifficultyCurve>, distance: &Distance) -> Difficulty {
+    curve.map_or_else(Difficulty::default, |curve| curve.sample(distance.metres()))
+}
+
+fn load_zombie_archetypes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ResourceHandle::<ZombieArchetypes>(
+        asset_server.load("zombies.archetypes.ron"),
+    ));
+}
+
 /// Spawns a single zombie instance.
 fn spawn_zombie(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    archetype: &ZombieArchetype,
     position: Vec3,
 ) {
-    let texture: Handle<Image> = asset_server.load("zombie_tilesheet.png");
-    let layout = TextureAtlasLayout::from_grid(Vec2::new(80.0, 110.0), 9, 3, None, None);
+    let texture: Handle<Image> = asset_server.load(&archetype.sprite_sheet);
+    let layout = TextureAtlasLayout::from_grid(
+        archetype.tile_size,
+        archetype.columns,
+        archetype.rows,
+        None,
+        None,
+    );
     let texture_atlas_layout = texture_atlas_layouts.add(layout);
+    let (first, last) = archetype.frames.jump;
+    let scale = ZOMBIE_SPRITE_SCALE * archetype.size.y / ENEMY_SIZE.y;
 
     commands.spawn((
         SpriteSheetBundle {
             texture,
             atlas: TextureAtlas {
                 layout: texture_atlas_layout,
-                index: 13, // Start with running animation
+                index: first, // Spawned in the air
             },
             transform: Transform::from_translation(position)
-                .with_scale(Vec3::new(0.70, 0.70, 1.0)), // Same scale as player
+                .with_scale(Vec3::new(scale, scale, 1.0)),
             ..default()
         },
         Enemy {
             velocity: Vec2::ZERO,
             is_grounded: false,
+            ground: None,
+            drop_through: None,
+            health: archetype.health,
+            stagger: finished_timer(),
+            speed: archetype.speed,
+            jump_strength: archetype.jump_strength,
+            damage: archetype.damage,
+            frames: archetype.frames,
         },
-        AnimationIndices { first: 13, last: 14 }, // Running animation
+        Contacts::default(),
+        BodySize(archetype.size),
+        Interpolated::new(position),
+        AnimationIndices { first, last },
         AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
         OnGameScreen,
     ));
 }
 
 fn zombie_movement_ai(
-    mut zombie_query: Query<(&mut Enemy, &Transform, &mut Sprite), Without<Platform>>,
+    mut zombie_query: Query<
+        (&mut Enemy, &Transform, &Contacts, &BodySize, &mut Sprite),
+        Without<Platform>,
+    >,
     player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
     platform_query: Query<&Transform, (With<Platform>, Without<Enemy>)>,
+    one_way_query: Query<(), With<OneWay>>,
+    difficulty_curve: Option<Res<DifficultyCurve>>,
+    distance: Res<Distance>,
     time: Res<Time>,
 ) {
+    let difficulty = current_difficulty(difficulty_curve.as_deref(), &distance);
     if let Ok(player_transform) = player_query.get_single() {
-        for (mut zombie, zombie_transform, mut sprite) in zombie_query.iter_mut() {
+        for (mut zombie, zombie_transform, contacts, size, mut sprite) in zombie_query.iter_mut() {
             if !zombie.is_grounded {
                 zombie.velocity.y -= GRAVITY * time.delta_seconds();
             }
 
+            // Reeling from a hit, so let the knockback play out
+            zombie.stagger.tick(time.delta());
+            if !zombie.stagger.finished() {
+                zombie.velocity.x *= STAGGER_DAMPING;
+                continue;
+            }
+
             let direction_to_player =
                 (player_transform.translation.x - zombie_transform.translation.x).signum();
-            zombie.velocity.x = direction_to_player * ENEMY_SPEED;
+            zombie.velocity.x = direction_to_player * zombie.speed * difficulty.zombie_speed_scale;
 
             if direction_to_player > 0.0 {
                 sprite.flip_x = false;
@@ -76,47 +159,46 @@ fn zombie_movement_ai(
             }
 
             if zombie.is_grounded {
+                // Drop through a one-way platform onto a player just below
+                let on_one_way = zombie
+                    .ground
+                    .is_some_and(|ground| one_way_query.contains(ground));
+                let player_below = player_transform.translation.y
+                    < zombie_transform.translation.y - PLAYER_SIZE.y
+                    && (player_transform.translation.x - zombie_transform.translation.x).abs()
+                        < size.x * 2.0;
+                if on_one_way && player_below {
+                    zombie.drop_through = zombie.ground;
+                    zombie.ground = None;
+                    zombie.is_grounded = false;
+                    continue;
+                }
+
                 let mut should_jump = false;
                 if player_transform.translation.y > zombie_transform.translation.y + PLAYER_SIZE.y
                 {
                     should_jump = true;
                 }
 
-                let probe_distance = direction_to_player * (ENEMY_SIZE.x / 2.0 + 10.0);
-                let probe_pos = Vec3::new(
-                    zombie_transform.translation.x + probe_distance,
-                    zombie_transform.translation.y - (ENEMY_SIZE.y / 2.0) - 5.0,
-                    0.0,
+                let probe_distance = direction_to_player * (size.x / 2.0 + 10.0);
+                let probe = Aabb::new(
+                    Vec2::new(
+                        zombie_transform.translation.x + probe_distance,
+                        zombie_transform.translation.y - (size.y / 2.0) - 5.0,
+                    ),
+                    Vec2::new(5.0, 5.0),
                 );
-                let probe_size = Vec2::new(5.0, 5.0);
-
-                let mut ground_ahead = false;
-                for platform_transform in &platform_query {
-                    let platform_size =
-                        Vec2::new(platform_transform.scale.x, platform_transform.scale.y);
-                    let platform_pos = platform_transform.translation;
+                let ground_ahead = platform_query.iter().any(|platform_transform| {
+                    probe.overlaps(&Aabb::from_transform(platform_transform))
+                });
 
-                    let x_collision = (probe_pos.x - probe_size.x / 2.0)
-                        < (platform_pos.x + platform_size.x / 2.0)
-                        && (probe_pos.x + probe_size.x / 2.0)
-                        > (platform_pos.x - platform_size.x / 2.0);
-                    let y_collision = (probe_pos.y - probe_size.y / 2.0)
-                        < (platform_pos.y + platform_size.y / 2.0)
-                        && (probe_pos.y + probe_size.y / 2.0)
-                        > (platform_pos.y - platform_size.y / 2.0);
-
-                    if x_collision && y_collision {
-                        ground_ahead = true;
-                        break;
-                    }
-                }
-
-                if !ground_ahead {
+                // Jump gaps and anything blocking the way
+                if !ground_ahead || contacts.is_touching_wall() {
                     should_jump = true;
                 }
 
                 if should_jump {
-                    zombie.velocity.y = ENEMY_JUMP_STRENGTH;
+                    zombie.velocity.y = zombie.jump_strength;
                     zombie.is_grounded = false;
                 }
             }
@@ -124,97 +206,105 @@ fn zombie_movement_ai(
     }
 }
 
-/// Applies the zombie's velocity to its transform.
-fn apply_velocity(mut query: Query<(&mut Transform, &Enemy)>, time: Res<Time>) {
-    for (mut transform, zombie) in &mut query {
-        transform.translation.x += zombie.velocity.x * time.delta_seconds();
-        transform.translation.y += zombie.velocity.y * time.delta_seconds();
+/// Moves zombies along with the platforms they're standing on.
+fn ride_platforms(
+    mut zombie_query: Query<(&mut Transform, &Enemy)>,
+    platform_query: Query<&MovingPlatform>,
+) {
+    for (mut transform, zombie) in &mut zombie_query {
+        if let Some(moving) = zombie.ground.and_then(|ground| platform_query.get(ground).ok()) {
+            transform.translation += moving.delta.extend(0.0);
+        }
     }
 }
 
-/// Handles collision between the zombie and platforms using manual AABB checks.
+/// Moves zombies by their velocity, landing them on floors and stopping them at walls and ceilings.
+#[cfg(not(feature = "rapier"))]
 fn zombie_platform_collision(
-    mut zombie_query: Query<(&mut Transform, &mut Enemy), Without<Platform>>,
-    platform_query: Query<&Transform, (With<Platform>, Without<Enemy>)>,
+    mut zombie_query: Query<(&mut Transform, &mut Enemy, &mut Contacts, &BodySize), Without<Platform>>,
+    platform_query: Query<(Entity, &Transform, Has<OneWay>), (With<Platform>, Without<Enemy>)>,
+    time: Res<Time>,
 ) {
-    for (mut zombie_transform, mut zombie) in zombie_query.iter_mut() {
-        zombie.is_grounded = false;
-        let zombie_size = ENEMY_SIZE;
-        let zombie_pos = zombie_transform.translation;
-
-        for platform_transform in &platform_query {
-            let platform_size =
-                Vec2::new(platform_transform.scale.x, platform_transform.scale.y);
-            let platform_pos = platform_transform.translation;
-
-            let x_collision = (zombie_pos.x - zombie_size.x / 2.0)
-                < (platform_pos.x + platform_size.x / 2.0)
-                && (zombie_pos.x + zombie_size.x / 2.0)
-                > (platform_pos.x - platform_size.x / 2.0);
-            let y_collision = (zombie_pos.y - zombie_size.y / 2.0)
-                < (platform_pos.y + platform_size.y / 2.0)
-                && (zombie_pos.y + zombie_size.y / 2.0)
-                > (platform_pos.y - platform_size.y / 2.0);
-
-            if x_collision && y_collision && zombie.velocity.y <= 0.0 {
-                let penetration = (platform_pos.y + platform_size.y / 2.0)
-                    - (zombie_pos.y - zombie_size.y / 2.0);
-                if penetration > 0.0 {
-                    zombie_transform.translation.y += penetration;
-                    zombie.velocity.y = 0.0;
-                    zombie.is_grounded = true;
-                }
-            }
-        }
+    for (mut zombie_transform, mut zombie, mut contacts, size) in zombie_query.iter_mut() {
+        let zombie = &mut *zombie;
+        zombie.ground = collision::move_body(
+            &mut zombie_transform,
+            **size,
+            &mut zombie.velocity,
+            time.delta_seconds(),
+            &platform_query,
+            &mut zombie.drop_through,
+            &mut contacts,
+        );
+        zombie.is_grounded = zombie.ground.is_some();
     }
 }
 
 /// Handles collision between the zombie and the player using manual AABB checks.
+/// Landing on a zombie's head kills it; touching it any other way hurts.
+#[cfg(not(feature = "rapier"))]
 fn zombie_player_collision(
-    mut player_query: Query<(&Transform, &mut Player), Without<Enemy>>,
-    zombie_query: Query<&Transform, (With<Enemy>, Without<Player>)>,
+    mut commands: Commands,
+    mut hits: EventWriter<PlayerHit>,
+    mut player_query: Query<
+        (&Transform, &mut Player, &BodySize, &ActivePowerUps),
+        Without<Enemy>,
+    >,
+    mut zombie_query: Query<
+        (Entity, &Transform, &mut Enemy, &BodySize),
+        (Without<Player>, Without<Dying>),
+    >,
 ) {
-    if let Ok((player_transform, mut player)) = player_query.get_single_mut() {
-        for zombie_transform in zombie_query.iter() {
-            let player_pos = player_transform.translation;
-            let zombie_pos = zombie_transform.translation;
-
-            let x_collision = (player_pos.x - PLAYER_SIZE.x / 2.0)
-                < (zombie_pos.x + ENEMY_SIZE.x / 2.0)
-                && (player_pos.x + PLAYER_SIZE.x / 2.0)
-                > (zombie_pos.x - ENEMY_SIZE.x / 2.0);
-            let y_collision = (player_pos.y - PLAYER_SIZE.y / 2.0)
-                < (zombie_pos.y + ENEMY_SIZE.y / 2.0)
-                && (player_pos.y + PLAYER_SIZE.y / 2.0)
-                > (zombie_pos.y - ENEMY_SIZE.y / 2.0);
+    if let Ok((player_transform, mut player, player_size, power_ups)) =
+        player_query.get_single_mut()
+    {
+        // Dashing through zombies is safe
+        if player.is_dashing() {
+            return;
+        }
+        let player_box = Aabb::new(player_transform.translation.truncate(), **player_size);
+        let mut contact_hits = ContactHits::default();
+        for (entity, zombie_transform, mut zombie, zombie_size) in zombie_query.iter_mut() {
+            let zombie_box = Aabb::new(zombie_transform.translation.truncate(), **zombie_size);
+            if !player_box.overlaps(&zombie_box) {
+                continue;
+            }
 
-            if x_collision && y_collision {
-                player.health -= 1.0;
-                // Since we are looping, break after the first hit to avoid multiple damage instances in one frame
-                break;
+            if collision::is_stomp(&player_box, player.velocity, &zombie_box, zombie.velocity) {
+                stomp_zombie(&mut commands, entity, &mut zombie, &mut player);
+                contact_hits.stomp();
+            } else {
+                contact_hits.touch(zombie_box.center, zombie.damage);
             }
         }
+
+        if let Some(hit) = contact_hits.hit(power_ups.is_active(PowerUp::Shield)) {
+            hits.send(hit);
+        }
     }
 }
 
 /// Animates the zombie's sprite based on its state.
 fn animate_zombie(
     time: Res<Time>,
-    mut query: Query<(
-        &mut AnimationIndices,
-        &mut AnimationTimer,
-        &mut TextureAtlas,
-        &Enemy,
-    )>,
+    mut query: Query<
+        (
+            &mut AnimationIndices,
+            &mut AnimationTimer,
+            &mut TextureAtlas,
+            &Enemy,
+        ),
+        Without<Dying>,
+    >,
 ) {
     for (mut indices, mut timer, mut atlas, zombie) in &mut query {
         timer.tick(time.delta());
         if timer.just_finished() {
             // TODO: Can be an index array to iterate
             let (first, last) = if !zombie.is_grounded {
-                (13, 14) // Jumping/Falling frame
+                zombie.frames.jump
             } else {
-                (9, 10) // Running frames
+                zombie.frames.run
             };
 
             if indices.first != first || indices.last != last {
@@ -232,11 +322,36 @@ fn animate_zombie(
     }
 }
 
+/// Plays a killed zombie's death: it recoils on its hurt frame, collapses
+/// onto its fallen one and fades out, then it's despawned.
+fn play_zombie_death(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Enemy, &mut Dying, &mut TextureAtlas, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    for (entity, zombie, mut dying, mut atlas, mut sprite) in &mut query {
+        dying.tick(time.delta());
+        atlas.index = if dying.fraction() < 0.5 {
+            zombie.frames.hurt
+        } else {
+            zombie.frames.fallen
+        };
+        sprite.color.set_a((2.0 * (1.0 - dying.fraction())).min(1.0));
+        if dying.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
 fn manage_zombie_population(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     config: Res<GameConfig>,
+    difficulty_curve: Option<Res<DifficultyCurve>>,
+    distance: Res<Distance>,
+    archetypes: Option<Res<ZombieArchetypes>>,
+    mut rng: ResMut<RunRng>,
     zombie_query: Query<(Entity, &Transform), With<Enemy>>,
     player_query: Query<&Transform, With<Player>>,
 ) {
@@ -252,17 +367,59 @@ fn manage_zombie_population(
         }
 
         // Spawn new zombies if count is less than max
-        if zombie_count < config.max_enemies {
+        let max_enemies = current_difficulty(difficulty_curve.as_deref(), &distance)
+            .max_enemies(config.max_enemies);
+        // Wait for the archetypes to load before spawning any
+        let Some(archetypes) = archetypes else {
+            return;
+        };
+        if zombie_count < max_enemies {
             // Add a small random offset to avoid spawning on top of each other
-            let random_offset = (rand::random::<f32>() * 200.0) - 100.0;
+            let random_offset = rng.spawns.gen_range(-100.0..100.0);
             let spawn_pos =
                 Vec3::new(player_transform.translation.x - 500.0 + random_offset, 200.0, 5.0);
-            spawn_zombie(
-                &mut commands,
-                &asset_server,
-                &mut texture_atlas_layouts,
-                spawn_pos,
-            );
+            if let Some(archetype) = archetypes.pick(&mut rng.spawns) {
+                spawn_zombie(
+                    &mut commands,
+                    &asset_server,
+                    &mut texture_atlas_layouts,
+                    archetype,
+                    spawn_pos,
+                );
+            }
+        }
+    }
+}
+
+/// Spawns the zombies placed by level chunks once they come into view.
+fn spawn_from_spawn_points(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    archetypes: Option<Res<ZombieArchetypes>>,
+    mut rng: ResMut<RunRng>,
+    spawn_point_query: Query<(Entity, &Transform), With<ZombieSpawnPoint>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Some(archetypes) = archetypes else {
+        return;
+    };
+    if let Ok(player_transform) = player_query.get_single() {
+        let screen_right_edge = player_transform.translation.x + (VIEWPORT_WIDTH / 2.0);
+        for (spawn_point_entity, spawn_point_transform) in spawn_point_query.iter() {
+            if spawn_point_transform.translation.x < screen_right_edge {
+                let spawn_pos = spawn_point_transform.translation.truncate().extend(5.0);
+                if let Some(archetype) = archetypes.pick(&mut rng.spawns) {
+                    spawn_zombie(
+                        &mut commands,
+                        &asset_server,
+                        &mut texture_atlas_layouts,
+                        archetype,
+                        spawn_pos,
+                    );
+                }
+                commands.entity(spawn_point_entity).despawn_recursive();
+            }
         }
     }
 }
@@ -0,0 +1,854 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::chunk::LevelChunk;
+use crate::difficulty::{Difficulty, DifficultyCurve};
+use crate::{
+    Crumble, Distance, PlatformMotion, GRAVITY, MAX_AIR_JUMPS, PICKUP_SIZE, PLATFORM_THICKNESS,
+    PLAYER_CROUCH_SIZE, PLAYER_FALL_GRAVITY, PLAYER_JUMP_STRENGTH, PLAYER_SIZE, PLAYER_SPEED,
+};
+
+/// Time spent running that counts as a full run-up.
+const RUN_UP_SECONDS: f32 = 0.5;
+
+/// The player's full-height jump, described by the same constants the
+/// physics uses. `gravity` applies on the way up, `fall_gravity` after the apex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JumpArc {
+    pub jump_speed: f32,
+    pub gravity: f32,
+    pub fall_gravity: f32,
+    pub run_speed: f32,
+}
+
+impl Default for JumpArc {
+    fn default() -> Self {
+        Self {
+            jump_speed: PLAYER_JUMP_STRENGTH,
+            gravity: GRAVITY,
+            fall_gravity: PLAYER_FALL_GRAVITY,
+            run_speed: PLAYER_SPEED,
+        }
+    }
+}
+
+impl JumpArc {
+    /// Highest the player's feet can get above the take-off height.
+    pub fn apex_height(&self) -> f32 {
+        self.jump_speed * self.jump_speed / (2.0 * self.gravity)
+    }
+
+    /// Floor to run along before jumping to be back at nearly full speed,
+    /// e.g. after crawling out from under an obstacle.
+    pub fn run_up(&self) -> f32 {
+        self.run_speed * RUN_UP_SECONDS
+    }
+
+    /// Horizontal distance covered at full speed before the arc comes back
+    /// down to `rise` above the take-off height (negative for drops).
+    /// Returns `None` if `rise` is above the apex.
+    pub fn reach(&self, rise: f32) -> Option<f32> {
+        self.air_time(rise).map(|time| self.run_speed * time)
+    }
+
+    /// Seconds from take-off until the arc comes back down to `rise` above
+    /// the take-off height. Returns `None` if `rise` is above the apex.
+    pub fn air_time(&self, rise: f32) -> Option<f32> {
+        let drop_from_apex = self.apex_height() - rise;
+        if drop_from_apex < 0.0 {
+            return None;
+        }
+        let rising = self.jump_speed / self.gravity;
+        let falling = (2.0 * drop_from_apex / self.fall_gravity).sqrt();
+        Some(rising + falling)
+    }
+
+    /// Height of the feet above the take-off height `time` seconds into the jump.
+    pub fn height_at(&self, time: f32) -> f32 {
+        let rising = self.jump_speed / self.gravity;
+        if time <= rising {
+            self.jump_speed * time - self.gravity * time * time / 2.0
+        } else {
+            let falling = time - rising;
+            self.apex_height() - self.fall_gravity * falling * falling / 2.0
+        }
+    }
+}
+
+/// A platform as the generator sees it: centre position and width.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlatformSpec {
+    pub position: Vec2,
+    pub width: f32,
+}
+
+impl PlatformSpec {
+    pub fn left_edge(&self) -> f32 {
+        self.position.x - self.width / 2.0
+    }
+
+    pub fn right_edge(&self) -> f32 {
+        self.position.x + self.width / 2.0
+    }
+
+    pub fn top(&self) -> f32 {
+        self.position.y + PLATFORM_THICKNESS / 2.0
+    }
+}
+
+/// A solid block hanging over a platform: centre position and size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObstacleSpec {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl ObstacleSpec {
+    pub fn bottom(&self) -> f32 {
+        self.position.y - self.size.y / 2.0
+    }
+}
+
+/// Generates platforms that the player can always reach from the previous one.
+///
+/// `safety_margin` is the fraction of the theoretical jump height and
+/// distance that is kept in reserve, e.g. `0.15` only uses 85% of the arc.
+///
+/// With probability `chunk_chance` a hand-authored chunk carrying all of
+/// `chunk_tags`, and no higher than `max_chunk_tier`, is placed instead of a
+/// single platform.
+///
+/// With probability `moving_chance` a generated platform moves. It starts
+/// where it was generated, so it is reachable from both neighbours whenever
+/// it is back at its origin. Otherwise, with probability `crumbling_chance`,
+/// it crumbles `crumble_delay` seconds after the player lands on it, or with
+/// probability `one_way_chance` it is a one-way platform.
+///
+/// A plain platform gets an overhead obstacle with probability
+/// `overhead_chance`, which can only be passed by sliding. The platform is
+/// stretched if needed to leave a full run-up on both sides. It
+/// may also get one pickup: ammo with probability `ammo_chance`, failing
+/// that a med-kit with `med_kit_chance`, failing that a max health upgrade
+/// with `upgrade_chance`, failing that a random power-up with
+/// `power_up_chance`.
+///
+/// With probability `coin_chance` a trail of `coin_count` coins is laid along
+/// the jump onto a generated platform.
+#[derive(Resource, Clone, Debug)]
+pub struct PlatformGenerator {
+    pub arc: JumpArc,
+    pub safety_margin: f32,
+    pub min_gap: f32,
+    pub max_gap: f32,
+    pub min_y: f32,
+    pub max_y: f32,
+    pub min_width: f32,
+    pub max_width: f32,
+    pub chunk_chance: f32,
+    pub chunk_tags: Vec<String>,
+    pub max_chunk_tier: u32,
+    pub moving_chance: f32,
+    pub max_travel: f32,
+    pub min_period: f32,
+    pub max_period: f32,
+    pub crumbling_chance: f32,
+    pub crumble_delay: f32,
+    pub one_way_chance: f32,
+    pub overhead_chance: f32,
+    pub min_overhead_width: f32,
+    pub max_overhead_width: f32,
+    pub ammo_chance: f32,
+    pub med_kit_chance: f32,
+    pub upgrade_chance: f32,
+    pub power_up_chance: f32,
+    pub coin_chance: f32,
+    pub coin_count: u32,
+}
+
+impl Default for PlatformGenerator {
+    fn default() -> Self {
+        Self {
+            arc: JumpArc::default(),
+            safety_margin: 0.15,
+            min_gap: 100.0,
+            max_gap: 250.0,
+            min_y: -250.0,
+            max_y: -50.0,
+            min_width: 100.0,
+            max_width: 250.0,
+            chunk_chance: 0.3,
+            chunk_tags: Vec::new(),
+            max_chunk_tier: u32::MAX,
+            moving_chance: 0.15,
+            max_travel: 150.0,
+            min_period: 2.0,
+            max_period: 4.0,
+            crumbling_chance: 0.0,
+            crumble_delay: 0.6,
+            one_way_chance: 0.2,
+            overhead_chance: 0.3,
+            min_overhead_width: 40.0,
+            max_overhead_width: 80.0,
+            ammo_chance: 0.2,
+            med_kit_chance: 0.1,
+            upgrade_chance: 0.02,
+            power_up_chance: 0.05,
+            coin_chance: 0.4,
+            coin_count: 5,
+        }
+    }
+}
+
+impl PlatformGenerator {
+    /// A copy of this generator with its ranges adjusted for `difficulty`.
+    pub fn at_difficulty(&self, difficulty: &Difficulty) -> Self {
+        Self {
+            min_gap: self.min_gap * difficulty.gap_scale,
+            max_gap: self.max_gap * difficulty.gap_scale,
+            // Never narrower than the player
+            min_width: (self.min_width * difficulty.width_scale).max(PLAYER_SIZE.x),
+            max_width: (self.max_width * difficulty.width_scale).max(PLAYER_SIZE.x + 1.0),
+            max_chunk_tier: self.max_chunk_tier.min(difficulty.max_chunk_tier),
+            crumbling_chance: difficulty.crumbling_chance,
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this generator adjusted for the difficulty where `previous`
+    /// ends. Going by the layout rather than by how far the player has got
+    /// keeps a seed producing the same layout however the player moves.
+    pub fn tuned_after(&self, curve: &DifficultyCurve, previous: &PlatformSpec) -> Self {
+        self.at_difficulty(&curve.sample(Distance(previous.right_edge()).metres()))
+    }
+
+    /// Highest step up from one platform to the next, after the margin.
+    pub fn max_rise(&self) -> f32 {
+        self.arc.apex_height() * (1.0 - self.safety_margin)
+    }
+
+    /// Widest gap that can be cleared with the given step up, after the margin.
+    pub fn max_reachable_gap(&self, rise: f32) -> f32 {
+        self.arc
+            .reach(rise)
+            .map_or(0.0, |reach| reach * (1.0 - self.safety_margin))
+    }
+
+    /// Whether `next` can be reached by jumping off the end of `previous`.
+    pub fn is_reachable(&self, previous: &PlatformSpec, next: &PlatformSpec) -> bool {
+        // Positions far into a run lose a little precision, so allow a hair of slack
+        const TOLERANCE: f32 = 0.1;
+        let rise = next.position.y - previous.position.y;
+        let gap = next.left_edge() - previous.right_edge();
+        rise <= self.max_rise() + TOLERANCE && gap <= self.max_reachable_gap(rise) + TOLERANCE
+    }
+
+    /// Picks the platform that follows `previous`.
+    pub fn next(&self, rng: &mut impl Rng, previous: &PlatformSpec) -> PlatformSpec {
+        let max_y = self.max_y.min(previous.position.y + self.max_rise());
+        let y = if max_y > self.min_y {
+            rng.gen_range(self.min_y..max_y)
+        } else {
+            max_y
+        };
+
+        let max_gap = self.max_gap.min(self.max_reachable_gap(y - previous.position.y));
+        let gap = if max_gap > self.min_gap {
+            rng.gen_range(self.min_gap..max_gap)
+        } else {
+            max_gap
+        };
+
+        let width = rng.gen_range(self.min_width..self.max_width);
+
+        PlatformSpec {
+            position: Vec2::new(previous.right_edge() + gap + width / 2.0, y),
+            width,
+        }
+    }
+
+    /// Rolls whether `next` should move, and if so how. Platforms either
+    /// slide back across the gap towards `previous` or sink towards `min_y`.
+    pub fn roll_motion(
+        &self,
+        rng: &mut impl Rng,
+        previous: &PlatformSpec,
+        next: &PlatformSpec,
+    ) -> Option<PlatformMotion> {
+        if !rng.gen_bool(self.moving_chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+
+        let horizontal = rng.gen_bool(0.5);
+        let room = if horizontal {
+            next.left_edge() - previous.right_edge()
+        } else {
+            next.position.y - self.min_y
+        };
+        let travel = room.min(self.max_travel) * rng.gen_range(0.5..1.0);
+        let period = rng.gen_range(self.min_period..self.max_period);
+        if travel < 20.0 {
+            return None;
+        }
+
+        let path = if horizontal {
+            Vec2::new(-travel, 0.0)
+        } else {
+            Vec2::new(0.0, -travel)
+        };
+        Some(PlatformMotion { path, period })
+    }
+
+    /// Rolls whether a platform should crumble. Generated ones don't respawn.
+    pub fn roll_crumble(&self, rng: &mut impl Rng) -> Option<Crumble> {
+        rng.gen_bool(self.crumbling_chance.clamp(0.0, 1.0) as f64)
+            .then_some(Crumble {
+                delay: self.crumble_delay,
+                respawn: None,
+            })
+    }
+
+    /// Rolls whether a platform should be one-way.
+    pub fn roll_one_way(&self, rng: &mut impl Rng) -> bool {
+        rng.gen_bool(self.one_way_chance.clamp(0.0, 1.0) as f64)
+    }
+
+    /// Rolls whether to hang an obstacle over `platform`. It hangs low
+    /// enough that only a crouched player fits underneath, and reaches higher
+    /// than a jump followed by every air jump the settings allow.
+    ///
+    /// The floor in front of it is left wide enough to land on and the floor
+    /// behind it long enough to get back up to speed before jumping, each a
+    /// full `JumpArc::run_up`. If `platform` is too narrow for that its
+    /// right edge is moved out, which leaves it just as reachable.
+    pub fn roll_overhead(
+        &self,
+        rng: &mut impl Rng,
+        platform: &mut PlatformSpec,
+    ) -> Option<ObstacleSpec> {
+        if !rng.gen_bool(self.overhead_chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+        let width = rng.gen_range(self.min_overhead_width..self.max_overhead_width);
+        let needed = width + 2.0 * self.arc.run_up();
+        if platform.width < needed {
+            platform.position.x += (needed - platform.width) / 2.0;
+            platform.width = needed;
+        }
+
+        let clearance = (PLAYER_CROUCH_SIZE.y + PLAYER_SIZE.y) / 2.0;
+        let jumps = (1 + MAX_AIR_JUMPS) as f32;
+        let height = self.arc.apex_height() * jumps + PLAYER_SIZE.y;
+        Some(ObstacleSpec {
+            position: Vec2::new(
+                platform.position.x,
+                platform.top() + clearance + height / 2.0,
+            ),
+            size: Vec2::new(width, height),
+        })
+    }
+
+    /// Rolls whether to put a pickup on `platform`, with probability
+    /// `chance`, and where. Pickups sit on top of the platform at a height a
+    /// crouched player can still reach. A platform narrower than a pickup
+    /// gets it in the middle.
+    pub fn roll_pickup(
+        &self,
+        rng: &mut impl Rng,
+        chance: f32,
+        platform: &PlatformSpec,
+    ) -> Option<Vec2> {
+        if !rng.gen_bool(chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+        let half_range = ((platform.width - PICKUP_SIZE.x) / 2.0).max(0.0);
+        let x = platform.position.x + rng.gen_range(-half_range..=half_range);
+        Some(Vec2::new(x, platform.top() + PICKUP_SIZE.y))
+    }
+
+    /// Rolls whether to lay coins along the jump from `previous` to `next`,
+    /// and where. They follow a full jump off the end of `previous` that
+    /// lands with the player within a `JumpArc::run_up` of `next`'s left
+    /// edge, short of any overhead obstacle, or on its middle if it's
+    /// narrower than that. The coins are at the height of the player's body.
+    pub fn roll_coin_arc(
+        &self,
+        rng: &mut impl Rng,
+        previous: &PlatformSpec,
+        next: &PlatformSpec,
+    ) -> Vec<Vec2> {
+        if !rng.gen_bool(self.coin_chance.clamp(0.0, 1.0) as f64) {
+            return Vec::new();
+        }
+        let Some(air_time) = self.arc.air_time(next.top() - previous.top()) else {
+            return Vec::new();
+        };
+
+        let take_off = Vec2::new(previous.right_edge(), previous.top() + PLAYER_SIZE.y / 2.0);
+        let landing =
+            (next.left_edge() + self.arc.run_up() - PLAYER_SIZE.x / 2.0).min(next.position.x);
+        let distance = landing - previous.right_edge();
+        (1..=self.coin_count)
+            .map(|index| {
+                let progress = index as f32 / (self.coin_count + 1) as f32;
+                let time = air_time * progress;
+                take_off + Vec2::new(distance * progress, self.arc.height_at(time))
+            })
+            .collect()
+    }
+
+    /// Rolls whether to place a chunk next, and if so which of the chunks
+    /// matching `chunk_tags`.
+    pub fn pick_chunk<'a>(
+        &self,
+        rng: &mut impl Rng,
+        chunks: &[&'a LevelChunk],
+    ) -> Option<&'a LevelChunk> {
+        if !rng.gen_bool(self.chunk_chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+        let candidates: Vec<_> = chunks
+            .iter()
+            .filter(|chunk| {
+                !chunk.platforms.is_empty()
+                    && chunk.has_tags(&self.chunk_tags)
+                    && chunk.tier() <= self.max_chunk_tier
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[rng.gen_range(0..candidates.len())])
+    }
+
+    /// Where to put the origin of `chunk` so its first platform is reachable
+    /// from `previous`.
+    pub fn place_chunk(
+        &self,
+        rng: &mut impl Rng,
+        previous: &PlatformSpec,
+        chunk: &LevelChunk,
+    ) -> Vec2 {
+        let entry = &chunk.platforms[0];
+        let landing = self.next(rng, previous);
+        Vec2::new(
+            landing.left_edge() - (entry.offset.x - entry.width / 2.0),
+            landing.position.y - entry.offset.y,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PLAYER_ACCELERATION, PLAYER_CRAWL_SPEED, PLAYER_DAMPING};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// Steps a jump off the end of `from` frame by frame, in the same order
+    /// as the game's systems, and reports whether it lands on top of `to`.
+    fn jump_lands(from: &PlatformSpec, to: &PlatformSpec, speed: f32) -> bool {
+        let top = to.position.y;
+        let mut feet = from.position.y;
+        let mut x = from.right_edge();
+        let mut velocity_y = PLAYER_JUMP_STRENGTH;
+
+        while velocity_y > 0.0 || feet > top - PLAYER_SIZE.y {
+            velocity_y -= if velocity_y > 0.0 {
+                GRAVITY * DT
+            } else {
+                PLAYER_FALL_GRAVITY * DT
+            };
+            x += speed * DT;
+            let previous_feet = feet;
+            feet += velocity_y * DT;
+
+            let over_platform = x + PLAYER_SIZE.x / 2.0 > to.left_edge()
+                && x - PLAYER_SIZE.x / 2.0 < to.right_edge();
+            if velocity_y <= 0.0 && previous_feet >= top && feet <= top && over_platform {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether some running speed up to `PLAYER_SPEED` makes the jump.
+    fn can_complete(from: &PlatformSpec, to: &PlatformSpec) -> bool {
+        can_complete_at(from, to, PLAYER_SPEED)
+    }
+
+    /// Whether some running speed up to `max_speed` makes the jump.
+    fn can_complete_at(from: &PlatformSpec, to: &PlatformSpec, max_speed: f32) -> bool {
+        (0..=30).any(|step| jump_lands(from, to, max_speed * step as f32 / 30.0))
+    }
+
+    /// Speed reached running `floor` pixels from a crawl, stepped like the
+    /// game's systems.
+    fn speed_after_crawl(floor: f32) -> f32 {
+        let mut speed = PLAYER_CRAWL_SPEED;
+        let mut x = 0.0;
+        while x < floor {
+            speed = ((speed + PLAYER_ACCELERATION * DT) * PLAYER_DAMPING).min(PLAYER_SPEED);
+            x += speed * DT;
+        }
+        speed
+    }
+
+    #[test]
+    fn apex_matches_constants() {
+        let arc = JumpArc::default();
+        let expected = PLAYER_JUMP_STRENGTH * PLAYER_JUMP_STRENGTH / (2.0 * GRAVITY);
+        assert!((arc.apex_height() - expected).abs() < f32::EPSILON * 100.0);
+        assert!(arc.reach(arc.apex_height() + 1.0).is_none());
+    }
+
+    #[test]
+    fn reach_grows_with_drop() {
+        let arc = JumpArc::default();
+        let level = arc.reach(0.0).unwrap();
+        assert!(arc.reach(-100.0).unwrap() > level);
+        assert!(arc.reach(100.0).unwrap() < level);
+    }
+
+    #[test]
+    fn heavier_fall_shortens_reach() {
+        let floaty = JumpArc {
+            fall_gravity: GRAVITY,
+            ..default()
+        };
+        // With even gravity the arc is symmetric: up and back down takes 2v/g
+        let symmetric = floaty.run_speed * 2.0 * floaty.jump_speed / floaty.gravity;
+        assert!((floaty.reach(0.0).unwrap() - symmetric).abs() < 1e-3);
+        assert!(JumpArc::default().reach(0.0).unwrap() < symmetric);
+    }
+
+    #[test]
+    fn every_generated_sequence_is_reachable() {
+        let generator = PlatformGenerator::default();
+
+        for seed in 0..2_000 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut previous = PlatformSpec {
+                position: Vec2::new(500.0, -150.0),
+                width: 200.0,
+            };
+
+            let mut previous_obstacle: Option<ObstacleSpec> = None;
+
+            for step in 0..100 {
+                let mut next = generator.next(&mut rng, &previous);
+                assert!(
+                    generator.is_reachable(&previous, &next),
+                    "seed {seed}, step {step}: {previous:?} -> {next:?}"
+                );
+                // Leaving a platform with an obstacle, the run-up starts from a crawl
+                let take_off_speed = previous_obstacle.map_or(PLAYER_SPEED, |obstacle| {
+                    let obstacle_right = obstacle.position.x + obstacle.size.x / 2.0;
+                    speed_after_crawl(previous.right_edge() - obstacle_right)
+                });
+                assert!(
+                    can_complete_at(&previous, &next, take_off_speed),
+                    "seed {seed}, step {step}: jump misses {previous:?} -> {next:?}"
+                );
+                assert!(next.position.y >= generator.min_y && next.position.y <= generator.max_y);
+
+                let obstacle = generator.roll_overhead(&mut rng, &mut next);
+                if let Some(obstacle) = obstacle {
+                    // Landing clear of the obstacle, without clipping its side
+                    let floor = obstacle.position.x - obstacle.size.x / 2.0 - next.left_edge();
+                    let width = floor - PLAYER_SIZE.x;
+                    let landing = PlatformSpec {
+                        position: Vec2::new(next.left_edge() + width / 2.0, next.position.y),
+                        width,
+                    };
+                    assert!(
+                        can_complete_at(&previous, &landing, take_off_speed),
+                        "seed {seed}, step {step}: no landing before {obstacle:?} on {next:?}"
+                    );
+                }
+                previous = next;
+                previous_obstacle = obstacle;
+            }
+        }
+    }
+
+    #[test]
+    fn layout_ignores_the_player() {
+        use crate::difficulty::DifficultyKey;
+        use crate::VIEWPORT_WIDTH;
+
+        let generator = PlatformGenerator::default();
+        let curve = DifficultyCurve {
+            keys: vec![
+                DifficultyKey {
+                    distance: 0.0,
+                    difficulty: Difficulty::default(),
+                },
+                DifficultyKey {
+                    distance: 300.0,
+                    difficulty: Difficulty {
+                        gap_scale: 2.0,
+                        width_scale: 0.5,
+                        ..default()
+                    },
+                },
+            ],
+        };
+        // Generates platforms the way `manage_platforms` does, topping up
+        // whenever the player gets within a screen of the last one
+        let layout = |seed: u64, player_step: f32| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut specs = vec![PlatformSpec {
+                position: Vec2::new(0.0, -250.0),
+                width: 800.0,
+            }];
+            let mut player_x = 0.0;
+            while specs.len() < 60 {
+                player_x += player_step;
+                let previous = specs[specs.len() - 1];
+                if previous.right_edge() < player_x + VIEWPORT_WIDTH / 2.0 {
+                    let tuned = generator.tuned_after(&curve, &previous);
+                    specs.push(tuned.next(&mut rng, &previous));
+                }
+            }
+            specs
+        };
+
+        for seed in 0..20 {
+            assert_eq!(layout(seed, 5.0), layout(seed, 400.0), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn harder_difficulty_stays_reachable() {
+        let generator = PlatformGenerator::default().at_difficulty(&Difficulty {
+            gap_scale: 2.0,
+            width_scale: 0.3,
+            ..default()
+        });
+
+        for seed in 0..500 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut previous = PlatformSpec {
+                position: Vec2::new(500.0, -150.0),
+                width: 200.0,
+            };
+            for _ in 0..100 {
+                let next = generator.next(&mut rng, &previous);
+                assert!(can_complete(&previous, &next), "seed {seed}: {previous:?} -> {next:?}");
+                assert!(next.width >= PLAYER_SIZE.x);
+                previous = next;
+            }
+        }
+    }
+
+    #[test]
+    fn moving_platforms_stay_between_neighbours() {
+        let generator = PlatformGenerator {
+            moving_chance: 1.0,
+            ..default()
+        };
+
+        for seed in 0..500 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let previous = PlatformSpec {
+                position: Vec2::new(500.0, -150.0),
+                width: 200.0,
+            };
+            let next = generator.next(&mut rng, &previous);
+            let Some(motion) = generator.roll_motion(&mut rng, &previous, &next) else {
+                continue;
+            };
+
+            let far_end = PlatformSpec {
+                position: next.position + motion.path,
+                ..next
+            };
+            assert!(far_end.left_edge() >= previous.right_edge());
+            assert!(far_end.position.y >= generator.min_y);
+            assert_eq!(motion.offset_at(0.0), Vec2::ZERO);
+            assert!((motion.offset_at(motion.period / 2.0) - motion.path).length() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn overheads_need_a_slide() {
+        let generator = PlatformGenerator {
+            overhead_chance: 1.0,
+            ..default()
+        };
+
+        for seed in 0..100 {
+            let mut platform = PlatformSpec {
+                position: Vec2::new(0.0, -150.0),
+                width: 250.0,
+            };
+            let mut rng = StdRng::seed_from_u64(seed);
+            let obstacle = generator.roll_overhead(&mut rng, &mut platform).unwrap();
+            let clearance = obstacle.bottom() - platform.top();
+            assert!(clearance > PLAYER_CROUCH_SIZE.y);
+            assert!(clearance < PLAYER_SIZE.y);
+            // Jumping can't clear it either, even chaining every air jump
+            let top = obstacle.bottom() + obstacle.size.y;
+            let jump_height = generator.arc.apex_height() * (1 + MAX_AIR_JUMPS) as f32;
+            assert!(top > platform.top() + jump_height + PLAYER_SIZE.y);
+            // There's a run-up's room to land in front of it and to speed up behind it
+            let half_width = obstacle.size.x / 2.0;
+            let run_up = generator.arc.run_up();
+            assert!(obstacle.position.x - half_width - platform.left_edge() >= run_up - 1e-3);
+            assert!(platform.right_edge() - (obstacle.position.x + half_width) >= run_up - 1e-3);
+        }
+    }
+
+    #[test]
+    fn narrow_platforms_stretch_to_the_right() {
+        let generator = PlatformGenerator {
+            overhead_chance: 1.0,
+            ..default()
+        };
+        let original = PlatformSpec {
+            position: Vec2::new(0.0, -150.0),
+            width: generator.min_overhead_width + PLAYER_SIZE.x,
+        };
+        let mut platform = original;
+        let mut rng = StdRng::seed_from_u64(0);
+        let obstacle = generator.roll_overhead(&mut rng, &mut platform).unwrap();
+        assert!((platform.left_edge() - original.left_edge()).abs() < 1e-3);
+        assert_eq!(platform.top(), original.top());
+        assert!(platform.width >= obstacle.size.x + 2.0 * generator.arc.run_up());
+    }
+
+    #[test]
+    fn pickups_sit_on_their_platform() {
+        let generator = PlatformGenerator::default();
+        let platform = PlatformSpec {
+            position: Vec2::new(0.0, -150.0),
+            width: generator.min_width,
+        };
+
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let position = generator.roll_pickup(&mut rng, 1.0, &platform).unwrap();
+            assert!(position.x - PICKUP_SIZE.x / 2.0 >= platform.left_edge());
+            assert!(position.x + PICKUP_SIZE.x / 2.0 <= platform.right_edge());
+            let bottom = position.y - PICKUP_SIZE.y / 2.0;
+            assert!(bottom > platform.top());
+            assert!(position.y + PICKUP_SIZE.y / 2.0 < platform.top() + PLAYER_CROUCH_SIZE.y);
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(generator.roll_pickup(&mut rng, 0.0, &platform).is_none());
+    }
+
+    #[test]
+    fn pickups_fit_on_narrow_platforms() {
+        let generator = PlatformGenerator::default();
+        let sliver = PlatformSpec {
+            position: Vec2::new(40.0, -150.0),
+            width: PICKUP_SIZE.x / 2.0,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let position = generator.roll_pickup(&mut rng, 1.0, &sliver).unwrap();
+        assert_eq!(position.x, sliver.position.x);
+    }
+
+    #[test]
+    fn height_at_follows_the_arc() {
+        let arc = JumpArc::default();
+        let rising = arc.jump_speed / arc.gravity;
+        assert_eq!(arc.height_at(0.0), 0.0);
+        assert!((arc.height_at(rising) - arc.apex_height()).abs() < 1e-3);
+        let landing = arc.air_time(-50.0).unwrap();
+        assert!((arc.height_at(landing) + 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn coins_trail_along_the_jump() {
+        let generator = PlatformGenerator {
+            coin_chance: 1.0,
+            ..default()
+        };
+        let previous = PlatformSpec {
+            position: Vec2::new(0.0, -200.0),
+            width: 200.0,
+        };
+        let next = PlatformSpec {
+            position: Vec2::new(400.0, -150.0),
+            width: 200.0,
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let coins = generator.roll_coin_arc(&mut rng, &previous, &next);
+        assert_eq!(coins.len(), generator.coin_count as usize);
+        for pair in coins.windows(2) {
+            assert!(pair[1].x > pair[0].x);
+        }
+        for coin in &coins {
+            assert!(coin.x > previous.right_edge() && coin.x < next.position.x);
+            // Above where the player's body would be standing on either platform
+            assert!(coin.y > previous.top() + PLAYER_SIZE.y / 2.0);
+            assert!(coin.y < previous.top() + generator.arc.apex_height() + PLAYER_SIZE.y);
+        }
+    }
+
+    #[test]
+    fn coins_land_short_of_overheads() {
+        let generator = PlatformGenerator {
+            coin_chance: 1.0,
+            overhead_chance: 1.0,
+            ..default()
+        };
+        let previous = PlatformSpec {
+            position: Vec2::new(0.0, -200.0),
+            width: 200.0,
+        };
+
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut next = generator.next(&mut rng, &previous);
+            let obstacle = generator.roll_overhead(&mut rng, &mut next).unwrap();
+            let coins = generator.roll_coin_arc(&mut rng, &previous, &next);
+            let last = coins.last().unwrap();
+            assert!(last.x + PLAYER_SIZE.x / 2.0 < obstacle.position.x - obstacle.size.x / 2.0);
+        }
+    }
+
+    #[test]
+    fn no_coins_for_an_unreachable_jump() {
+        let generator = PlatformGenerator {
+            coin_chance: 1.0,
+            ..default()
+        };
+        let previous = PlatformSpec {
+            position: Vec2::new(0.0, -250.0),
+            width: 200.0,
+        };
+        let too_high = PlatformSpec {
+            position: Vec2::new(300.0, 250.0),
+            width: 200.0,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(generator
+            .roll_coin_arc(&mut rng, &previous, &too_high)
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_platforms_out_of_reach() {
+        let generator = PlatformGenerator::default();
+        let from = PlatformSpec {
+            position: Vec2::new(0.0, -250.0),
+            width: 200.0,
+        };
+        let too_high = PlatformSpec {
+            position: Vec2::new(300.0, -50.0),
+            width: 200.0,
+        };
+        let too_far = PlatformSpec {
+            position: Vec2::new(1_000.0, -250.0),
+            width: 200.0,
+        };
+        assert!(!generator.is_reachable(&from, &too_high));
+        assert!(!generator.is_reachable(&from, &too_far));
+    }
+}
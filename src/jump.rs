@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+/// Coyote time and jump buffering.
+///
+/// A jump press is remembered for the buffer window, so pressing just before
+/// landing still jumps. Jumping stays possible for the coyote window after
+/// walking off a ledge. A jump uses up both, so one press is one jump.
+#[derive(Clone, Debug)]
+pub struct JumpGrace {
+    coyote: Timer,
+    buffer: Timer,
+    /// Grounded, or still inside the coyote window after leaving the ground.
+    can_jump: bool,
+    /// A press is waiting inside the buffer window.
+    buffered: bool,
+}
+
+impl JumpGrace {
+    pub fn new(coyote_seconds: f32, buffer_seconds: f32) -> Self {
+        Self {
+            coyote: Timer::from_seconds(coyote_seconds, TimerMode::Once),
+            buffer: Timer::from_seconds(buffer_seconds, TimerMode::Once),
+            can_jump: false,
+            buffered: false,
+        }
+    }
+
+    /// Advances both windows. Call once per tick, before `press` and
+    /// `try_jump`, with whether the body was standing on something.
+    pub fn tick(&mut self, delta: Duration, grounded: bool) {
+        self.buffer.tick(delta);
+        if self.buffer.finished() {
+            self.buffered = false;
+        }
+
+        if grounded {
+            // The coyote window only starts counting once the ground is left
+            self.coyote.reset();
+            self.can_jump = true;
+        } else {
+            self.coyote.tick(delta);
+            if self.coyote.finished() {
+                self.can_jump = false;
+            }
+        }
+    }
+
+    /// Records a jump press.
+    pub fn press(&mut self) {
+        self.buffer.reset();
+        self.buffered = true;
+    }
+
+    /// Whether a buffered press can jump now. If so, the press and the
+    /// coyote window are used up.
+    pub fn try_jump(&mut self) -> bool {
+        if self.buffered && self.can_jump {
+            self.consume();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Uses up a buffered press whether or not the body can jump from the
+    /// ground, for jumping in mid-air.
+    pub fn take_press(&mut self) -> bool {
+        std::mem::take(&mut self.buffered)
+    }
+
+    /// Throws away any buffered press and coyote time, e.g. when leaving the
+    /// ground some way other than walking off it.
+    pub fn consume(&mut self) {
+        self.buffered = false;
+        self.can_jump = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_micros(16_667);
+    const COYOTE_SECONDS: f32 = 0.1;
+    const BUFFER_SECONDS: f32 = 0.1;
+
+    fn grace() -> JumpGrace {
+        JumpGrace::new(COYOTE_SECONDS, BUFFER_SECONDS)
+    }
+
+    fn ticks(grace: &mut JumpGrace, count: usize, grounded: bool) {
+        for _ in 0..count {
+            grace.tick(TICK, grounded);
+        }
+    }
+
+    #[test]
+    fn jumps_from_the_ground() {
+        let mut grace = grace();
+        grace.tick(TICK, true);
+        grace.press();
+        assert!(grace.try_jump());
+    }
+
+    #[test]
+    fn cannot_jump_without_a_press() {
+        let mut grace = grace();
+        ticks(&mut grace, 10, true);
+        assert!(!grace.try_jump());
+    }
+
+    #[test]
+    fn one_press_is_one_jump() {
+        let mut grace = grace();
+        grace.tick(TICK, true);
+        grace.press();
+        assert!(grace.try_jump());
+
+        // Still grounded the next tick, and the press is still inside the buffer window
+        grace.tick(TICK, true);
+        assert!(!grace.try_jump());
+        grace.tick(TICK, false);
+        assert!(!grace.try_jump());
+    }
+
+    #[test]
+    fn buffered_press_jumps_on_landing() {
+        let mut grace = grace();
+        grace.tick(TICK, false);
+        grace.press();
+        assert!(!grace.try_jump());
+
+        ticks(&mut grace, 3, false);
+        grace.tick(TICK, true);
+        assert!(grace.try_jump());
+    }
+
+    #[test]
+    fn buffered_press_expires() {
+        let mut grace = grace();
+        grace.tick(TICK, false);
+        grace.press();
+
+        ticks(&mut grace, 10, false);
+        grace.tick(TICK, true);
+        assert!(!grace.try_jump());
+    }
+
+    #[test]
+    fn coyote_time_after_walking_off_a_ledge() {
+        let mut grace = grace();
+        ticks(&mut grace, 3, true);
+        ticks(&mut grace, 3, false);
+        grace.press();
+        assert!(grace.try_jump());
+    }
+
+    #[test]
+    fn coyote_time_expires() {
+        let mut grace = grace();
+        ticks(&mut grace, 3, true);
+        ticks(&mut grace, 10, false);
+        grace.press();
+        assert!(!grace.try_jump());
+    }
+
+    #[test]
+    fn coyote_time_starts_on_leaving_the_ground() {
+        let mut grace = grace();
+        // Standing around for longer than the window doesn't use it up
+        ticks(&mut grace, 60, true);
+        ticks(&mut grace, 3, false);
+        grace.press();
+        assert!(grace.try_jump());
+    }
+
+    #[test]
+    fn no_coyote_time_after_jumping() {
+        let mut grace = grace();
+        grace.tick(TICK, true);
+        grace.press();
+        assert!(grace.try_jump());
+
+        grace.tick(TICK, false);
+        grace.press();
+        assert!(!grace.try_jump());
+    }
+
+    #[test]
+    fn consume_cancels_coyote_time() {
+        let mut grace = grace();
+        grace.tick(TICK, true);
+        grace.consume();
+
+        grace.tick(TICK, false);
+        grace.press();
+        assert!(!grace.try_jump());
+    }
+
+    #[test]
+    fn take_press_works_in_mid_air_once() {
+        let mut grace = grace();
+        ticks(&mut grace, 10, false);
+        grace.press();
+        assert!(!grace.try_jump());
+        assert!(grace.take_press());
+        assert!(!grace.take_press());
+    }
+}
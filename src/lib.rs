@@ -1,7 +1,21 @@
-mod background;
+pub mod archetype;
+pub mod chunk;
+pub mod collision;
+pub mod difficulty;
+pub mod generator;
+pub mod jump;
+pub mod powerup;
+pub mod ron_asset;
+pub mod save;
 
 use bevy::prelude::*;
+use archetype::ZombieFrames;
+use jump::JumpGrace;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
 use std::collections::VecDeque;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum GameState {
@@ -19,17 +33,91 @@ pub enum PlayerState {
     Idle,
     Running,
     Jumping,
+    AirJumping,
     Falling,
+    WallSliding,
+    Crouching,
+    Sliding,
 }
 
 #[derive(Component)]
 pub struct Player {
     pub velocity: Vec2,
     pub is_grounded: bool,
-    pub coyote_time: Timer,
-    pub jump_buffer: Timer,
+    pub jump_grace: JumpGrace,
+    /// Extra jumps allowed before landing again, from `GameConfig` at spawn.
+    pub air_jumps: u32,
+    /// Air jumps used since last standing on something.
+    pub air_jumps_used: u32,
+    /// In the air from a jump the player made, which letting go of jump cuts
+    /// short. Bounces and knockback aren't cut.
+    pub jumping: bool,
+    /// Running after a wall jump or a hit, during which left/right input is
+    /// ignored so the player is carried away.
+    pub input_lock: Timer,
+    /// Running while the player dashes.
+    pub dash: Timer,
+    /// Running until the player can dash again.
+    pub dash_cooldown: Timer,
+    /// Ducking with a `PLAYER_CROUCH_SIZE` collision box.
+    pub crouching: bool,
+    /// Running until the player can attack again.
+    pub attack_cooldown: Timer,
+    pub ammo: u32,
+    /// Running until the player can fire again.
+    pub fire_cooldown: Timer,
+    /// Running after the player is hit, during which further hits are ignored.
+    pub invulnerable: Timer,
     pub health: f32,
+    pub max_health: f32,
     pub state: PlayerState,
+    /// The platform the player is standing on, if any.
+    pub ground: Option<Entity>,
+    /// A one-way platform the player is dropping through.
+    pub drop_through: Option<Entity>,
+}
+
+impl Player {
+    /// Mid-dash the player ignores gravity and the speed cap, and zombies
+    /// can't hurt them.
+    pub fn is_dashing(&self) -> bool {
+        !self.dash.finished()
+    }
+
+    /// Restores up to `amount` health, never going over `max_health`.
+    pub fn heal(&mut self, amount: f32) {
+        self.health = (self.health + amount).min(self.max_health);
+    }
+}
+
+/// Sent when something hits the player for a fixed amount of damage.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct PlayerHit {
+    pub damage: f32,
+    /// Where the hit came from, to knock the player away from it.
+    pub source: Vec2,
+}
+
+/// The size of an entity's collision box, centred on its translation.
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct BodySize(pub Vec2);
+
+/// Keyboard state sampled every frame and consumed by the fixed-timestep
+/// player systems, so presses aren't lost or repeated between ticks.
+#[derive(Resource, Default)]
+pub struct PlayerInput {
+    /// -1.0 for left, 1.0 for right, 0.0 for neither or both.
+    pub direction: f32,
+    /// Set when jump is pressed; cleared once a tick has handled it.
+    pub jump_pressed: bool,
+    pub jump_held: bool,
+    pub down_held: bool,
+    /// Set when dash is pressed; cleared once a tick has handled it.
+    pub dash_pressed: bool,
+    /// Set when attack is pressed; cleared once a tick has handled it.
+    pub attack_pressed: bool,
+    /// Set when fire is pressed; cleared once a tick has handled it.
+    pub fire_pressed: bool,
 }
 
 #[derive(Component)]
@@ -45,16 +133,114 @@ pub struct AnimationTimer(pub Timer);
 pub struct Enemy {
     pub velocity: Vec2,
     pub is_grounded: bool,
+    /// The platform the zombie is standing on, if any.
+    pub ground: Option<Entity>,
+    /// A one-way platform the zombie is dropping through.
+    pub drop_through: Option<Entity>,
+    pub health: f32,
+    /// Running while the zombie reels from a hit and doesn't chase the player.
+    pub stagger: Timer,
+    /// Chasing speed, jump and contact damage from its `ZombieArchetype`.
+    pub speed: f32,
+    pub jump_strength: f32,
+    pub damage: f32,
+    pub frames: ZombieFrames,
+}
+
+/// A zombie that has been killed, playing its death animation until the
+/// timer runs out and it's despawned.
+#[derive(Component, Deref, DerefMut)]
+pub struct Dying(pub Timer);
+
+/// A bullet fired by the player. It flies straight until it hits a zombie
+/// or a platform, or leaves the screen.
+#[derive(Component)]
+pub struct Projectile {
+    pub velocity: Vec2,
+}
+
+/// Ammo lying on a rooftop, sized by its transform's scale.
+#[derive(Component)]
+pub struct AmmoPickup {
+    pub amount: u32,
+}
+
+/// Health lying on a rooftop, sized by its transform's scale.
+#[derive(Component, Clone, Copy, Debug)]
+pub enum HealthPickup {
+    /// Heals this much, up to the player's max health.
+    MedKit(f32),
+    /// Raises max health by this much, and heals the same amount.
+    Upgrade(f32),
+}
+
+/// A coin floating along a jump. Once the player comes close it is pulled
+/// towards them until collected.
+#[derive(Component, Default)]
+pub struct Coin {
+    pub attracted: bool,
+}
+
+/// A short-lived attack box held in front of the player. Each zombie it
+/// touches is hit once.
+#[derive(Component)]
+pub struct MeleeHitbox {
+    pub lifetime: Timer,
+    /// Where the box sits relative to the player.
+    pub offset: Vec2,
+    pub size: Vec2,
+    /// 1.0 when swinging right, -1.0 when swinging left.
+    pub direction: f32,
+    /// Zombies already hit by this swing.
+    pub hit: Vec<Entity>,
 }
 
 #[derive(Resource)]
 pub struct GameConfig {
     pub max_enemies: u32,
+    /// Jumps the player gets in mid-air, e.g. 1 for a double jump. At most
+    /// `MAX_AIR_JUMPS`.
+    pub air_jumps: u32,
+    /// Seed to use for the next run. `None` rolls a fresh one each run.
+    pub seed: Option<u64>,
+    /// Whether the player slowly heals over time.
+    pub regeneration: bool,
+    /// Only chunks tagged `biome:<name>` are used. `None` mixes every biome.
+    pub biome: Option<String>,
 }
 
 impl Default for GameConfig {
     fn default() -> Self {
-        Self { max_enemies: 3 }
+        Self {
+            max_enemies: 3,
+            air_jumps: 1,
+            seed: None,
+            regeneration: false,
+            biome: None,
+        }
+    }
+}
+
+/// The seed the current run was started with.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunSeed(pub u64);
+
+/// Seeded random streams for the current run.
+///
+/// Platforms and spawns draw from separate streams so that the layout for a
+/// seed doesn't depend on how many zombies happened to spawn along the way.
+#[derive(Resource)]
+pub struct RunRng {
+    pub platforms: StdRng,
+    pub spawns: StdRng,
+}
+
+impl RunRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            platforms: StdRng::seed_from_u64(seed),
+            spawns: StdRng::seed_from_u64(seed ^ 0x9E37_79B9_7F4A_7C15),
+        }
     }
 }
 
@@ -64,6 +250,115 @@ pub struct OnSettingsMenu;
 #[derive(Component)]
 pub struct Platform;
 
+/// A platform that can be jumped up through and dropped down through; it
+/// only catches things falling onto its top.
+#[derive(Component)]
+pub struct OneWay;
+
+/// Back-and-forth movement for a platform: out from its origin to
+/// `origin + path` and back again every `period` seconds.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PlatformMotion {
+    pub path: Vec2,
+    pub period: f32,
+}
+
+impl PlatformMotion {
+    /// Offset from the origin `elapsed` seconds into the motion.
+    pub fn offset_at(&self, elapsed: f32) -> Vec2 {
+        let phase = elapsed / self.period * std::f32::consts::TAU;
+        self.path * (0.5 - 0.5 * phase.cos())
+    }
+}
+
+/// A platform following a `PlatformMotion`. `delta` is how far it moved this
+/// frame, so anything standing on it can be carried along.
+#[derive(Component)]
+pub struct MovingPlatform {
+    pub origin: Vec2,
+    pub motion: PlatformMotion,
+    pub elapsed: f32,
+    pub delta: Vec2,
+}
+
+/// How a platform crumbles: it starts shaking when the player lands on it,
+/// falls away `delay` seconds later and, if `respawn` is set, comes back that
+/// many seconds after it has fallen.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Crumble {
+    pub delay: f32,
+    #[serde(default)]
+    pub respawn: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrumbleState {
+    Intact,
+    Shaking,
+    Falling,
+    Gone,
+}
+
+/// A platform that crumbles. While falling or gone it loses its `Platform`
+/// component, so nothing collides with it.
+#[derive(Component)]
+pub struct CrumblingPlatform {
+    pub origin: Vec2,
+    pub crumble: Crumble,
+    pub state: CrumbleState,
+    pub timer: Timer,
+    pub fall_speed: f32,
+}
+
+impl CrumblingPlatform {
+    pub fn new(origin: Vec2, crumble: Crumble) -> Self {
+        Self {
+            origin,
+            crumble,
+            state: CrumbleState::Intact,
+            timer: Timer::from_seconds(crumble.delay, TimerMode::Once),
+            fall_speed: 0.0,
+        }
+    }
+}
+
+/// Systems that move platforms. Riders are carried after this has run.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlatformMovement;
+
+/// Gameplay translation at the last two fixed ticks. Sprites are drawn
+/// between the two so movement looks smooth at any frame rate.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Interpolated {
+    pub previous: Vec3,
+    pub current: Vec3,
+}
+
+impl Interpolated {
+    pub fn new(translation: Vec3) -> Self {
+        Self {
+            previous: translation,
+            current: translation,
+        }
+    }
+}
+
+/// Moves `Interpolated` entities to where they should be drawn this frame.
+/// Anything following them on screen, like the camera, runs after this.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Interpolation;
+
+/// Drains the player's health while they overlap it. Size comes from the
+/// transform's scale, like `Platform`.
+#[derive(Component)]
+pub struct Hazard {
+    pub damage_per_second: f32,
+}
+
+/// Marks where a chunk wants a zombie; one is spawned once the player gets close.
+#[derive(Component)]
+pub struct ZombieSpawnPoint;
+
 #[derive(Component)]
 pub struct OnGameScreen;
 
@@ -73,27 +368,82 @@ pub struct OnPauseMenu;
 #[derive(Component)]
 pub struct HealthBar;
 
+/// Fills up as the dash cooldown runs out.
+#[derive(Component)]
+pub struct DashCooldownBar;
+
 #[derive(Component)]
 pub struct DistanceText;
 
+#[derive(Component)]
+pub struct AmmoText;
+
+#[derive(Component)]
+pub struct CoinText;
+
 #[derive(Resource, Default)]
 pub struct Distance(pub f32);
 
+impl Distance {
+    /// Distance in metres, as shown in the HUD.
+    pub fn metres(&self) -> f32 {
+        self.0 / 10.0
+    }
+}
+
+/// Coins collected this run, banked into the save file at game over.
+#[derive(Resource, Default)]
+pub struct Coins(pub u32);
+
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct PlatformQueue(pub VecDeque<Entity>);
 
 // --- CONSTANTS ---
+/// Gameplay tick rate. Movement constants are tuned for this.
+pub const FIXED_TIMESTEP_HZ: f64 = 60.0;
 pub const GRAVITY: f32 = 1600.0;
 pub const PLAYER_JUMP_STRENGTH: f32 = 650.0;
+/// Gravity on the player once they're past the apex, so jumps come down snappier.
+pub const PLAYER_FALL_GRAVITY: f32 = 2400.0;
+/// Upward speed a jump is cut to when the jump key is let go, so a tap is a short hop.
+pub const PLAYER_JUMP_CUT_SPEED: f32 = 300.0;
 pub const PLAYER_SPEED: f32 = 300.0;
+pub const PLAYER_ACCELERATION: f32 = 2000.0;
+/// Horizontal speed kept each tick, which also settles running at `PLAYER_SPEED`.
+pub const PLAYER_DAMPING: f32 = 0.9;
+/// Crouched players can only shuffle along this fast under their own power.
+pub const PLAYER_CRAWL_SPEED: f32 = 100.0;
+/// Most air jumps the settings allow, for a quadruple jump.
+pub const MAX_AIR_JUMPS: u32 = 3;
 pub const PLAYER_SIZE: Vec2 = Vec2::new(50.0, 80.0);
+pub const PLAYER_CROUCH_SIZE: Vec2 = Vec2::new(50.0, 40.0);
 pub const PLATFORM_THICKNESS: f32 = 20.0;
+pub const PICKUP_SIZE: Vec2 = Vec2::new(20.0, 20.0);
+pub const COIN_SIZE: Vec2 = Vec2::new(14.0, 14.0);
+/// How far above a platform's top an entity can be and still count as standing on it.
+pub const GROUND_TOLERANCE: f32 = 1.0;
 pub const VIEWPORT_WIDTH: f32 = 800.0;
 
 // --- ENEMY CONSTANTS ---
+// Defaults for the plain walker; see `archetype::ZombieArchetype`
 pub const ENEMY_SPEED: f32 = 270.0; // A bit slower than the player
 pub const ENEMY_JUMP_STRENGTH: f32 = 650.0; // Can't jump as high as the player
 pub const ENEMY_SIZE: Vec2 = PLAYER_SIZE; // Same size as player
+pub const ENEMY_CONTACT_DAMAGE: f32 = 15.0;
+
+/// Picks the seed for a new run and inserts fresh `RunSeed`/`RunRng` resources.
+pub fn seed_new_run(mut commands: Commands, config: Res<GameConfig>) {
+    let seed = config.seed.unwrap_or_else(rand::random);
+    commands.insert_resource(RunSeed(seed));
+    commands.insert_resource(RunRng::from_seed(seed));
+}
+
+/// A timer that has already run out, for things that start off inactive.
+pub fn finished_timer() -> Timer {
+    let mut timer = Timer::new(Duration::ZERO, TimerMode::Once);
+    timer.tick(Duration::ZERO);
+    timer
+}
 
 pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
     for entity in &to_despawn {
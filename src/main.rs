@@ -1,20 +1,33 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 use bevy::prelude::*;
 
 mod background;
+mod coins;
+mod combat;
+mod effects;
 mod enemy;
 mod platform;
 mod player;
+#[cfg(feature = "rapier")]
+mod rapier;
+mod timestep;
 mod ui;
 
 use crate::enemy::EnemyPlugin;
 use background::BackgroundPlugin;
+use coins::CoinPlugin;
+use combat::CombatPlugin;
+use effects::EffectPlugin;
 use platform::PlatformPlugin;
 use player::PlayerPlugin;
-use fuzzy_runner::{Distance, GameState, GameConfig, OnGameScreen, PlatformQueue};
+use timestep::TimestepPlugin;
+use fuzzy_runner::{seed_new_run, Coins, Distance, GameState, GameConfig, OnGameScreen, PlatformQueue};
 use ui::UiPlugin;
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Rust Platformer!".into(),
@@ -24,22 +37,36 @@ fn main() {
         }))
         .init_state::<GameState>()
         .init_resource::<Distance>()
+        .init_resource::<Coins>()
         .insert_resource(GameConfig::default())
+        .add_systems(Startup, seed_new_run)
         .add_plugins((
             PlayerPlugin,
             PlatformPlugin,
             UiPlugin,
             EnemyPlugin,
             BackgroundPlugin,
+            TimestepPlugin,
+            CombatPlugin,
+            CoinPlugin,
+            EffectPlugin,
         ))
-        .add_systems(OnEnter(GameState::Restart), cleanup_game_session)
-        .run();
+        .add_systems(
+            OnEnter(GameState::Restart),
+            (cleanup_game_session, seed_new_run),
+        );
+
+    #[cfg(feature = "rapier")]
+    app.add_plugins(rapier::RapierBackendPlugin);
+
+    app.run();
 }
 
 fn cleanup_game_session(
     mut commands: Commands,
     game_screen_entities: Query<Entity, With<OnGameScreen>>,
     mut distance: ResMut<Distance>,
+    mut coins: ResMut<Coins>,
     mut platform_queue: ResMut<PlatformQueue>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
@@ -50,6 +77,7 @@ fn cleanup_game_session(
 
     // 2. Reset game-specific resources to their default values
     distance.0 = 0.0;
+    coins.0 = 0;
     platform_queue.0.clear();
 
     // 3. Immediately transition to the Playing state to start a new game
@@ -1,28 +1,86 @@
+use fuzzy_runner::chunk::{ChunkLibrary, LevelChunk};
+use fuzzy_runner::difficulty::DifficultyCurve;
+use fuzzy_runner::generator::{ObstacleSpec, PlatformGenerator, PlatformSpec};
+use fuzzy_runner::powerup::PowerUp;
+use fuzzy_runner::ron_asset::{sync_asset_resource, ResourceHandle, RonAssetLoader};
 use fuzzy_runner::{
-    GameState, OnGameScreen, Platform, PlatformQueue, Player, PLATFORM_THICKNESS, VIEWPORT_WIDTH,
+    AmmoPickup, Coin, Crumble, CrumbleState, CrumblingPlatform, GameConfig, GameState, Hazard,
+    HealthPickup, Interpolated, MovingPlatform, OnGameScreen, OneWay, Platform, PlatformMotion,
+    PlatformMovement, PlatformQueue, Player, RunRng, ZombieSpawnPoint, GRAVITY, PICKUP_SIZE,
+    COIN_SIZE, PLATFORM_THICKNESS, VIEWPORT_WIDTH,
 };
+use bevy::asset::{LoadState, LoadedFolder, RecursiveDependencyLoadState};
 use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const MOVING_COLOR: Color = Color::rgb(0.1, 0.7, 0.8);
+const CRUMBLING_COLOR: Color = Color::rgb(0.55, 0.5, 0.45);
+const ONE_WAY_COLOR: Color = Color::rgb(0.95, 0.75, 0.2);
+const OVERHEAD_COLOR: Color = Color::rgb(0.3, 0.35, 0.4);
+const HAZARD_COLOR: Color = Color::rgb(0.9, 0.1, 0.4);
+const AMMO_COLOR: Color = Color::rgb(1.0, 0.9, 0.3);
+const AMMO_PER_PICKUP: u32 = 5;
+const MED_KIT_COLOR: Color = Color::rgb(0.9, 0.95, 0.9);
+const UPGRADE_COLOR: Color = Color::rgb(1.0, 0.3, 0.5);
+const COIN_COLOR: Color = Color::rgb(0.2, 1.0, 0.8);
+const MED_KIT_HEALING: f32 = 30.0;
+const UPGRADE_MAX_HEALTH: f32 = 25.0;
+const CRUMBLE_FALL_SECONDS: f32 = 1.0;
 
 pub struct PlatformPlugin;
 
 impl Plugin for PlatformPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<PlatformQueue>()
+            .init_resource::<PlatformGenerator>()
+            .init_asset::<LevelChunk>()
+            .init_asset_loader::<RonAssetLoader<LevelChunk>>()
+            .init_asset::<DifficultyCurve>()
+            .init_asset_loader::<RonAssetLoader<DifficultyCurve>>()
+            .add_systems(Startup, (load_chunks, load_difficulty_curve))
             .add_systems(OnEnter(GameState::Playing), setup_platforms)
             .add_systems(
                 Update,
-                manage_platforms.run_if(in_state(GameState::Playing)),
+                (
+                    sync_asset_resource::<DifficultyCurve>,
+                    manage_platforms.run_if(in_state(GameState::Playing)),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                (move_platforms, crumble_platforms)
+                    .in_set(PlatformMovement)
+                    .run_if(in_state(GameState::Playing)),
             );
     }
 }
 
+fn load_chunks(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ChunkLibrary(asset_server.load_folder("chunks")));
+}
+
+fn load_difficulty_curve(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ResourceHandle::<DifficultyCurve>(
+        asset_server.load("difficulty.curve.ron"),
+    ));
+}
+
 fn setup_platforms(
     mut commands: Commands,
     mut platform_queue: ResMut<PlatformQueue>,
+    mut generator: ResMut<PlatformGenerator>,
+    config: Res<GameConfig>,
     platform_query: Query<Entity, With<Platform>>,
 ) {
     if platform_query.iter().next().is_none() {
         platform_queue.0.clear();
+        generator.chunk_tags = config
+            .biome
+            .iter()
+            .map(|biome| format!("biome:{biome}"))
+            .collect();
 
         let first_platform = spawn_platform(&mut commands, Vec2::new(0.0, -250.0), 800.0);
         platform_queue.0.push_back(first_platform);
@@ -53,40 +111,476 @@ pub fn spawn_platform(commands: &mut Commands, position: Vec2, width: f32) -> En
         .id()
 }
 
+pub fn spawn_moving_platform(
+    commands: &mut Commands,
+    position: Vec2,
+    width: f32,
+    motion: PlatformMotion,
+) -> Entity {
+    let entity = spawn_platform(commands, position, width);
+    commands.entity(entity).insert((
+        Sprite {
+            color: MOVING_COLOR,
+            ..default()
+        },
+        MovingPlatform {
+            origin: position,
+            motion,
+            elapsed: 0.0,
+            delta: Vec2::ZERO,
+        },
+        Interpolated::new(position.extend(0.0)),
+    ));
+    entity
+}
+
+pub fn spawn_one_way_platform(commands: &mut Commands, position: Vec2, width: f32) -> Entity {
+    let entity = spawn_platform(commands, position, width);
+    commands.entity(entity).insert((
+        Sprite {
+            color: ONE_WAY_COLOR,
+            ..default()
+        },
+        OneWay,
+    ));
+    entity
+}
+
+pub fn spawn_crumbling_platform(
+    commands: &mut Commands,
+    position: Vec2,
+    width: f32,
+    crumble: Crumble,
+) -> Entity {
+    let entity = spawn_platform(commands, position, width);
+    commands.entity(entity).insert((
+        Sprite {
+            color: CRUMBLING_COLOR,
+            ..default()
+        },
+        CrumblingPlatform::new(position, crumble),
+        Interpolated::new(position.extend(0.0)),
+    ));
+    entity
+}
+
+/// A sign or pipe hanging over a platform. It's solid like any other platform.
+pub fn spawn_overhead_obstacle(commands: &mut Commands, obstacle: &ObstacleSpec) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: OVERHEAD_COLOR,
+                    ..default()
+                },
+                transform: Transform {
+                    translation: obstacle.position.extend(0.0),
+                    scale: obstacle.size.extend(1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            Platform,
+            OnGameScreen,
+        ))
+        .id()
+}
+
+fn spawn_pickup(commands: &mut Commands, position: Vec2, color: Color) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                transform: Transform {
+                    translation: position.extend(0.5),
+                    scale: PICKUP_SIZE.extend(1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            OnGameScreen,
+        ))
+        .id()
+}
+
+pub fn spawn_ammo_pickup(commands: &mut Commands, position: Vec2) -> Entity {
+    let entity = spawn_pickup(commands, position, AMMO_COLOR);
+    commands.entity(entity).insert(AmmoPickup {
+        amount: AMMO_PER_PICKUP,
+    });
+    entity
+}
+
+pub fn spawn_health_pickup(
+    commands: &mut Commands,
+    position: Vec2,
+    pickup: HealthPickup,
+) -> Entity {
+    let color = match pickup {
+        HealthPickup::MedKit(_) => MED_KIT_COLOR,
+        HealthPickup::Upgrade(_) => UPGRADE_COLOR,
+    };
+    let entity = spawn_pickup(commands, position, color);
+    commands.entity(entity).insert(pickup);
+    entity
+}
+
+pub fn spawn_power_up(commands: &mut Commands, position: Vec2, power_up: PowerUp) -> Entity {
+    let entity = spawn_pickup(commands, position, power_up.color());
+    commands.entity(entity).insert(power_up);
+    entity
+}
+
+pub fn spawn_coin(commands: &mut Commands, position: Vec2) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: COIN_COLOR,
+                    ..default()
+                },
+                transform: Transform {
+                    translation: position.extend(0.5),
+                    scale: COIN_SIZE.extend(1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            Coin::default(),
+            OnGameScreen,
+        ))
+        .id()
+}
+
+pub fn spawn_hazard(commands: &mut Commands, position: Vec2, size: Vec2, damage_per_second: f32) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: HAZARD_COLOR,
+                    ..default()
+                },
+                transform: Transform {
+                    translation: position.extend(0.5),
+                    scale: size.extend(1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            Hazard { damage_per_second },
+            OnGameScreen,
+        ))
+        .id()
+}
+
+/// Spawns every platform, hazard and spawn point of `chunk` relative to
+/// `origin`, queueing them all for cleanup.
+fn spawn_chunk(
+    commands: &mut Commands,
+    platform_queue: &mut PlatformQueue,
+    chunk: &LevelChunk,
+    origin: Vec2,
+) {
+    for platform in &chunk.platforms {
+        let position = origin + platform.offset;
+        let entity = match (platform.motion, platform.crumble) {
+            // A chunk can't ask for both, see `LevelChunk::validate`
+            (Some(motion), _) => spawn_moving_platform(commands, position, platform.width, motion),
+            (None, Some(crumble)) => {
+                spawn_crumbling_platform(commands, position, platform.width, crumble)
+            }
+            (None, None) if platform.one_way => {
+                spawn_one_way_platform(commands, position, platform.width)
+            }
+            (None, None) => spawn_platform(commands, position, platform.width),
+        };
+        // Moving and crumbling platforms can be one-way too, but keep their own tint
+        if platform.one_way && (platform.motion.is_some() || platform.crumble.is_some()) {
+            commands.entity(entity).insert(OneWay);
+        }
+        platform_queue.push_back(entity);
+    }
+    for hazard in &chunk.hazards {
+        let entity = spawn_hazard(
+            commands,
+            origin + hazard.offset,
+            hazard.size,
+            hazard.damage_per_second,
+        );
+        platform_queue.push_back(entity);
+    }
+    for &spawn in &chunk.spawns {
+        let entity = commands
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(
+                    (origin + spawn).extend(0.0),
+                )),
+                ZombieSpawnPoint,
+                OnGameScreen,
+            ))
+            .id();
+        platform_queue.push_back(entity);
+    }
+}
+
+/// The chunks available to the generator, sorted by path so that a seed
+/// always picks the same ones. `None` while the library is still loading.
+fn loaded_chunks<'a>(
+    asset_server: &AssetServer,
+    library: &ChunkLibrary,
+    folders: &Assets<LoadedFolder>,
+    chunks: &'a Assets<LevelChunk>,
+) -> Option<Vec<&'a LevelChunk>> {
+    match asset_server.load_state(&library.0) {
+        LoadState::Loaded => {}
+        // No chunk folder, so only generate single platforms
+        LoadState::Failed => return Some(Vec::new()),
+        LoadState::NotLoaded | LoadState::Loading => return None,
+    }
+    if !matches!(
+        asset_server.recursive_dependency_load_state(&library.0),
+        RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed
+    ) {
+        return None;
+    }
+
+    let folder = folders.get(&library.0)?;
+    let mut loaded: Vec<_> = folder
+        .handles
+        .iter()
+        .filter_map(|handle| handle.id().try_typed::<LevelChunk>().ok())
+        .filter_map(|id| Some((asset_server.get_path(id)?.to_string(), chunks.get(id)?)))
+        .collect();
+    loaded.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Some(loaded.into_iter().map(|(_, chunk)| chunk).collect())
+}
+
+fn move_platforms(mut query: Query<(&mut Transform, &mut MovingPlatform)>, time: Res<Time>) {
+    for (mut transform, mut platform) in &mut query {
+        platform.elapsed += time.delta_seconds();
+        let position = platform.origin + platform.motion.offset_at(platform.elapsed);
+        platform.delta = position - transform.translation.truncate();
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+/// Shakes a platform once the player has landed on it, then drops it and
+/// either despawns it or brings it back after its respawn delay.
+fn crumble_platforms(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+        &mut CrumblingPlatform,
+    )>,
+    player_query: Query<&Player>,
+    time: Res<Time>,
+) {
+    let player_ground = player_query.get_single().ok().and_then(|player| player.ground);
+
+    for (entity, mut transform, mut sprite, mut visibility, mut platform) in &mut query {
+        match platform.state {
+            CrumbleState::Intact => {
+                if player_ground == Some(entity) {
+                    platform.state = CrumbleState::Shaking;
+                    platform.timer = Timer::from_seconds(platform.crumble.delay, TimerMode::Once);
+                }
+            }
+            CrumbleState::Shaking => {
+                platform.timer.tick(time.delta());
+                // Shake harder and glow redder the closer it is to giving way
+                let progress = platform.timer.fraction();
+                let shake = (platform.timer.elapsed_secs() * 60.0).sin() * 3.0 * progress;
+                transform.translation.x = platform.origin.x + shake;
+                sprite.color = Color::rgb(
+                    0.55 + 0.4 * progress,
+                    0.5 - 0.35 * progress,
+                    0.45 - 0.3 * progress,
+                );
+
+                if platform.timer.finished() {
+                    transform.translation.x = platform.origin.x;
+                    platform.state = CrumbleState::Falling;
+                    platform.fall_speed = 0.0;
+                    platform.timer = Timer::from_seconds(CRUMBLE_FALL_SECONDS, TimerMode::Once);
+                    commands.entity(entity).remove::<Platform>();
+                }
+            }
+            CrumbleState::Falling => {
+                platform.timer.tick(time.delta());
+                platform.fall_speed += GRAVITY * time.delta_seconds();
+                transform.translation.y -= platform.fall_speed * time.delta_seconds();
+
+                if platform.timer.finished() {
+                    match platform.crumble.respawn {
+                        Some(respawn) => {
+                            *visibility = Visibility::Hidden;
+                            platform.state = CrumbleState::Gone;
+                            platform.timer = Timer::from_seconds(respawn, TimerMode::Once);
+                        }
+                        None => commands.entity(entity).despawn_recursive(),
+                    }
+                }
+            }
+            CrumbleState::Gone => {
+                platform.timer.tick(time.delta());
+                if platform.timer.finished() {
+                    transform.translation.x = platform.origin.x;
+                    transform.translation.y = platform.origin.y;
+                    *visibility = Visibility::Inherited;
+                    sprite.color = CRUMBLING_COLOR;
+                    platform.state = CrumbleState::Intact;
+                    commands.entity(entity).insert(Platform);
+                }
+            }
+        }
+    }
+}
+
+/// Generates the platform after `previous` and spawns it, moving, crumbling
+/// or one-way if the generator rolls for it. Plain platforms may get an
+/// overhead obstacle and a pickup, which are queued for cleanup after the platform.
+fn spawn_generated_platform(
+    commands: &mut Commands,
+    platform_queue: &mut PlatformQueue,
+    generator: &PlatformGenerator,
+    rng: &mut impl Rng,
+    previous: &PlatformSpec,
+) {
+    let mut next = generator.next(rng, previous);
+    if let Some(motion) = generator.roll_motion(rng, previous, &next) {
+        platform_queue.push_back(spawn_moving_platform(
+            commands,
+            next.position,
+            next.width,
+            motion,
+        ));
+    } else if let Some(crumble) = generator.roll_crumble(rng) {
+        platform_queue.push_back(spawn_crumbling_platform(
+            commands,
+            next.position,
+            next.width,
+            crumble,
+        ));
+    } else if generator.roll_one_way(rng) {
+        platform_queue.push_back(spawn_one_way_platform(commands, next.position, next.width));
+    } else {
+        // Rolled first, as it may stretch the platform to fit
+        let obstacle = generator.roll_overhead(rng, &mut next);
+        platform_queue.push_back(spawn_platform(commands, next.position, next.width));
+        if let Some(obstacle) = obstacle {
+            platform_queue.push_back(spawn_overhead_obstacle(commands, &obstacle));
+        }
+        if let Some(position) = generator.roll_pickup(rng, generator.ammo_chance, &next) {
+            platform_queue.push_back(spawn_ammo_pickup(commands, position));
+        } else if let Some(position) = generator.roll_pickup(rng, generator.med_kit_chance, &next) {
+            let pickup = HealthPickup::MedKit(MED_KIT_HEALING);
+            platform_queue.push_back(spawn_health_pickup(commands, position, pickup));
+        } else if let Some(position) = generator.roll_pickup(rng, generator.upgrade_chance, &next) {
+            let pickup = HealthPickup::Upgrade(UPGRADE_MAX_HEALTH);
+            platform_queue.push_back(spawn_health_pickup(commands, position, pickup));
+        } else if let Some(position) = generator.roll_pickup(rng, generator.power_up_chance, &next) {
+            let power_up = *PowerUp::ALL.choose(rng).unwrap();
+            platform_queue.push_back(spawn_power_up(commands, position, power_up));
+        }
+    }
+    for position in generator.roll_coin_arc(rng, previous, &next) {
+        platform_queue.push_back(spawn_coin(commands, position));
+    }
+}
+
+/// The generator's view of a platform. Moving and crumbling platforms are
+/// taken at their origin, which is where the generator made sure they were
+/// reachable.
+fn platform_spec(
+    transform: &Transform,
+    moving: Option<&MovingPlatform>,
+    crumbling: Option<&CrumblingPlatform>,
+) -> PlatformSpec {
+    let origin = moving
+        .map(|moving| moving.origin)
+        .or(crumbling.map(|crumbling| crumbling.origin));
+    PlatformSpec {
+        position: origin.unwrap_or(transform.translation.truncate()),
+        width: transform.scale.x,
+    }
+}
+
 fn manage_platforms(
     mut commands: Commands,
     mut platform_queue: ResMut<PlatformQueue>,
+    mut rng: ResMut<RunRng>,
+    generator: Res<PlatformGenerator>,
+    difficulty_curve: Option<Res<DifficultyCurve>>,
+    asset_server: Res<AssetServer>,
+    chunk_library: Res<ChunkLibrary>,
+    folders: Res<Assets<LoadedFolder>>,
+    chunks: Res<Assets<LevelChunk>>,
     player_query: Query<&Transform, With<Player>>,
-    platform_query: Query<&Transform, With<Platform>>,
+    queued_query: Query<&Transform>,
+    platform_query: Query<
+        (
+            &Transform,
+            Option<&MovingPlatform>,
+            Option<&CrumblingPlatform>,
+        ),
+        Or<(With<Platform>, With<CrumblingPlatform>)>,
+    >,
 ) {
     if let Ok(player_transform) = player_query.get_single() {
-        // Despawn old platforms
-        if let Some(&first_platform_entity) = platform_queue.front() {
-            if let Ok(platform_transform) = platform_query.get(first_platform_entity) {
-                let platform_right_edge =
-                    platform_transform.translation.x + (platform_transform.scale.x / 2.0);
-                let screen_left_edge =
-                    player_transform.translation.x - (VIEWPORT_WIDTH / 2.0) - 50.0;
-                if platform_right_edge < screen_left_edge {
-                    commands.entity(first_platform_entity).despawn_recursive();
+        // Despawn old platforms, hazards and spawn points
+        if let Some(&first_entity) = platform_queue.front() {
+            match queued_query.get(first_entity) {
+                Ok(transform) => {
+                    let right_edge = transform.translation.x + (transform.scale.x / 2.0);
+                    let screen_left_edge =
+                        player_transform.translation.x - (VIEWPORT_WIDTH / 2.0) - 50.0;
+                    if right_edge < screen_left_edge {
+                        commands.entity(first_entity).despawn_recursive();
+                        platform_queue.pop_front();
+                    }
+                }
+                // Already gone, e.g. a spawn point that has been used
+                Err(_) => {
                     platform_queue.pop_front();
                 }
             }
         }
 
-        // Spawn new platforms
-        if let Some(&last_platform_entity) = platform_queue.back() {
-            if let Ok(platform_transform) = platform_query.get(last_platform_entity) {
-                let platform_right_edge =
-                    platform_transform.translation.x + (platform_transform.scale.x / 2.0);
-                let screen_right_edge = player_transform.translation.x + (VIEWPORT_WIDTH / 2.0);
-                if platform_right_edge < screen_right_edge {
-                    let new_x = platform_right_edge + (rand::random::<f32>() * 150.0) + 100.0;
-                    let new_y = -250.0 + (rand::random::<f32>() * 200.0);
-                    let new_width = 100.0 + (rand::random::<f32>() * 150.0);
-                    let new_platform_entity =
-                        spawn_platform(&mut commands, Vec2::new(new_x, new_y), new_width);
-                    platform_queue.push_back(new_platform_entity);
+        // Hold off until the chunks and curve are in, so a seed always produces the same layout
+        let Some(available_chunks) =
+            loaded_chunks(&asset_server, &chunk_library, &folders, &chunks)
+        else {
+            return;
+        };
+        let Some(difficulty_curve) = difficulty_curve else {
+            return;
+        };
+
+        // Spawn new platforms after the furthest one
+        let last_platform = platform_queue
+            .iter()
+            .filter_map(|&entity| platform_query.get(entity).ok())
+            .map(|(transform, moving, crumbling)| platform_spec(transform, moving, crumbling))
+            .max_by(|a, b| a.right_edge().total_cmp(&b.right_edge()));
+        if let Some(previous) = last_platform {
+            let screen_right_edge = player_transform.translation.x + (VIEWPORT_WIDTH / 2.0);
+            if previous.right_edge() < screen_right_edge {
+                let generator = generator.tuned_after(&difficulty_curve, &previous);
+                if let Some(chunk) = generator.pick_chunk(&mut rng.platforms, &available_chunks) {
+                    let origin = generator.place_chunk(&mut rng.platforms, &previous, chunk);
+                    spawn_chunk(&mut commands, &mut platform_queue, chunk, origin);
+                } else {
+                    spawn_generated_platform(
+                        &mut commands,
+                        &mut platform_queue,
+                        &generator,
+                        &mut rng.platforms,
+                        &previous,
+                    );
                 }
             }
         }
@@ -1,13 +1,48 @@
 use bevy::prelude::*;
+use bevy::sprite::Anchor;
+#[cfg(not(feature = "rapier"))]
+use fuzzy_runner::collision;
+use fuzzy_runner::collision::{Aabb, Contacts};
+use fuzzy_runner::jump::JumpGrace;
+use fuzzy_runner::powerup::{ActivePowerUps, PowerUp};
+#[cfg(not(feature = "rapier"))]
+use fuzzy_runner::Hazard;
 use fuzzy_runner::{
-    AnimationIndices, AnimationTimer, Distance, GameState, OnGameScreen, Platform, Player,
-    PlayerState, GRAVITY, PLATFORM_THICKNESS, PLAYER_JUMP_STRENGTH, PLAYER_SIZE, PLAYER_SPEED,
+    finished_timer, AnimationIndices, AnimationTimer, BodySize, Distance, GameConfig, GameState,
+    Interpolated, Interpolation, MovingPlatform, OnGameScreen, OneWay, Platform, PlatformMovement,
+    Player, PlayerInput, PlayerState, GRAVITY, GROUND_TOLERANCE, PLATFORM_THICKNESS,
+    PLAYER_ACCELERATION, PLAYER_CRAWL_SPEED, PLAYER_CROUCH_SIZE, PLAYER_DAMPING,
+    PLAYER_FALL_GRAVITY, PLAYER_JUMP_CUT_SPEED, PLAYER_JUMP_STRENGTH, PLAYER_SIZE, PLAYER_SPEED,
 };
 
-const PLAYER_ACCELERATION: f32 = 2000.0;
-const PLAYER_DAMPING: f32 = 0.9;
+#[cfg(feature = "rapier")]
+use crate::rapier::{check_collisions, check_hazards};
+
+/// Multiplier on acceleration and top speed during a speed boost.
+const SPEED_BOOST_SCALE: f32 = 1.5;
+/// Weaker damping while crouched, so crouching at a run slides along.
+const PLAYER_SLIDE_DAMPING: f32 = 0.97;
+/// On-screen height of the player sprite, a 110px frame scaled by 0.7.
+const PLAYER_SPRITE_HEIGHT: f32 = 77.0;
 const COYOTE_TIME_SECONDS: f32 = 0.1;
 const JUMP_BUFFER_SECONDS: f32 = 0.1;
+/// Fastest the player slides down a wall they're pressing into.
+const WALL_SLIDE_SPEED: f32 = 120.0;
+/// How long after a wall jump left/right input is ignored, so the kick
+/// carries the player away from the wall.
+const WALL_JUMP_LOCK_SECONDS: f32 = 0.15;
+const DASH_SPEED: f32 = 900.0;
+const DASH_SECONDS: f32 = 0.15;
+/// Counted from the start of the dash.
+const DASH_COOLDOWN_SECONDS: f32 = 1.0;
+const DASH_KEYS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+const JUMP_KEYS: [KeyCode; 3] = [KeyCode::KeyW, KeyCode::ArrowUp, KeyCode::Space];
+const ATTACK_KEYS: [KeyCode; 2] = [KeyCode::KeyJ, KeyCode::KeyX];
+const FIRE_KEYS: [KeyCode; 2] = [KeyCode::KeyK, KeyCode::KeyC];
+const STARTING_AMMO: u32 = 10;
+const PLAYER_MAX_HEALTH: f32 = 100.0;
+/// Health per second restored when regeneration is turned on.
+const REGENERATION_PER_SECOND: f32 = 1.0;
 
 pub struct PlayerPlugin;
 
@@ -15,20 +50,32 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Playing), spawn_player)
             .add_systems(OnEnter(GameState::Paused), zero_player_velocity_on_pause)
+            .init_resource::<PlayerInput>()
             .add_systems(
                 Update,
                 (
+                    read_input,
                     update_player_state,
                     animate_sprite.after(update_player_state),
+                    camera_follow_player.after(Interpolation),
+                    check_for_death,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
                     handle_input,
+                    update_crouch,
                     apply_forces,
-                    apply_velocity.before(check_collisions),
+                    ride_platform,
                     check_collisions,
-                    camera_follow_player.after(apply_velocity),
-                    check_for_death.after(check_collisions),
+                    check_hazards,
+                    regenerate_health,
                     update_distance,
                 )
                     .chain()
+                    .after(PlatformMovement)
                     .run_if(in_state(GameState::Playing)),
             );
     }
@@ -39,6 +86,7 @@ fn spawn_player(
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     player_query: Query<Entity, With<Player>>,
+    config: Res<GameConfig>,
 ) {
     if player_query.get_single().is_err() {
         let texture: Handle<Image> = asset_server.load("player_tilesheet.png");
@@ -47,6 +95,7 @@ fn spawn_player(
 
         let ground_y = -250.0;
         let player_start_y = ground_y + (PLATFORM_THICKNESS / 2.0) + (PLAYER_SIZE.y / 2.0);
+        let player_start = Vec3::new(100.0, player_start_y, 1.0);
 
         commands.spawn((
             SpriteSheetBundle {
@@ -55,18 +104,35 @@ fn spawn_player(
                     layout: texture_atlas_layout,
                     index: 0, // Start at the idle frame
                 },
-                transform: Transform::from_xyz(100.0, player_start_y, 1.0)
+                transform: Transform::from_translation(player_start)
                     .with_scale(Vec3::new(0.70, 0.70, 1.0)), // Scale the sprite down a bit
                 ..default()
             },
             Player {
                 velocity: Vec2::ZERO,
                 is_grounded: true,
-                coyote_time: Timer::from_seconds(COYOTE_TIME_SECONDS, TimerMode::Once),
-                jump_buffer: Timer::from_seconds(JUMP_BUFFER_SECONDS, TimerMode::Once),
-                health: 100.0,
+                jump_grace: JumpGrace::new(COYOTE_TIME_SECONDS, JUMP_BUFFER_SECONDS),
+                air_jumps: config.air_jumps,
+                air_jumps_used: 0,
+                jumping: false,
+                input_lock: finished_timer(),
+                dash: finished_timer(),
+                dash_cooldown: finished_timer(),
+                crouching: false,
+                attack_cooldown: finished_timer(),
+                ammo: STARTING_AMMO,
+                fire_cooldown: finished_timer(),
+                invulnerable: finished_timer(),
+                health: PLAYER_MAX_HEALTH,
+                max_health: PLAYER_MAX_HEALTH,
                 state: PlayerState::Idle,
+                ground: None,
+                drop_through: None,
             },
+            Contacts::default(),
+            BodySize(PLAYER_SIZE),
+            ActivePowerUps::default(),
+            Interpolated::new(player_start),
             AnimationIndices { first: 0, last: 0 },
             AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)), // Faster timer for more frames
             OnGameScreen,
@@ -74,18 +140,29 @@ fn spawn_player(
     }
 }
 
-fn update_player_state(mut query: Query<&mut Player>) {
-    if let Ok(mut player) = query.get_single_mut() {
+fn update_player_state(input: Res<PlayerInput>, mut query: Query<(&mut Player, &Contacts)>) {
+    if let Ok((mut player, contacts)) = query.get_single_mut() {
         // Determine the new state based on a clear priority:
         // 1. In the air (Jumping/Falling)
-        // 2. On the ground and moving (Running)
-        // 3. On the ground and still (Idle)
+        // 2. Crouched (Sliding/Crouching)
+        // 3. On the ground and moving (Running)
+        // 4. On the ground and still (Idle)
         let new_state = if !player.is_grounded {
-            if player.velocity.y > 0.0 {
+            if is_wall_sliding(&player, contacts, &input) {
+                PlayerState::WallSliding
+            } else if player.velocity.y > 0.0 && player.air_jumps_used > 0 {
+                PlayerState::AirJumping
+            } else if player.velocity.y > 0.0 {
                 PlayerState::Jumping
             } else {
                 PlayerState::Falling
             }
+        } else if player.crouching {
+            if player.velocity.x.abs() > PLAYER_CRAWL_SPEED {
+                PlayerState::Sliding
+            } else {
+                PlayerState::Crouching
+            }
         } else if player.velocity.x.abs() > 0.0 {
             PlayerState::Running
         } else {
@@ -115,7 +192,11 @@ fn animate_sprite(
                 PlayerState::Idle => (0, 0),
                 PlayerState::Running => (9, 10),
                 PlayerState::Jumping => (1, 1),
+                PlayerState::AirJumping => (7, 8),
                 PlayerState::Falling => (2, 2),
+                PlayerState::WallSliding => (20, 20),
+                PlayerState::Crouching => (3, 3),
+                PlayerState::Sliding => (19, 19),
             };
 
             if indices.first != first || indices.last != last {
@@ -141,6 +222,19 @@ fn update_distance(player_query: Query<&Transform, With<Player>>, mut distance:
     }
 }
 
+fn regenerate_health(
+    config: Res<GameConfig>,
+    mut player_query: Query<&mut Player>,
+    time: Res<Time>,
+) {
+    if !config.regeneration {
+        return;
+    }
+    if let Ok(mut player) = player_query.get_single_mut() {
+        player.heal(REGENERATION_PER_SECOND * time.delta_seconds());
+    }
+}
+
 fn check_for_death(
     player_query: Query<(&Transform, &Player)>,
     camera_query: Query<&Transform, With<Camera>>,
@@ -168,104 +262,275 @@ fn camera_follow_player(
     }
 }
 
-fn handle_input(
+/// Samples the keyboard every frame for the fixed-timestep systems.
+fn read_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_query: Query<(&mut Player, &mut Sprite)>,
-    time: Res<Time>,
+    mut input: ResMut<PlayerInput>,
+    mut sprite_query: Query<&mut Sprite, With<Player>>,
 ) {
-    if let Ok((mut player, mut sprite)) = player_query.get_single_mut() {
-        player.coyote_time.tick(time.delta());
-        player.jump_buffer.tick(time.delta());
+    let mut direction = 0.0;
+    if keyboard_input.pressed(KeyCode::KeyD) || keyboard_input.pressed(KeyCode::ArrowRight) {
+        direction += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft) {
+        direction -= 1.0;
+    }
+    input.direction = direction;
 
-        let mut direction = 0.0;
-        if keyboard_input.pressed(KeyCode::KeyD) || keyboard_input.pressed(KeyCode::ArrowRight) {
-            direction += 1.0;
+    if let Ok(mut sprite) = sprite_query.get_single_mut() {
+        if direction > 0.0 {
             sprite.flip_x = false; // Face right
-        }
-        if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft) {
-            direction -= 1.0;
+        } else if direction < 0.0 {
             sprite.flip_x = true; // Face left
         }
+    }
 
-        player.velocity.x += direction * PLAYER_ACCELERATION * time.delta_seconds();
+    // Latched until a tick handles it, in case no tick runs this frame
+    input.jump_pressed |= keyboard_input.any_just_pressed(JUMP_KEYS);
+    input.jump_held = keyboard_input.any_pressed(JUMP_KEYS);
+    input.dash_pressed |= keyboard_input.any_just_pressed(DASH_KEYS);
+    input.attack_pressed |= keyboard_input.any_just_pressed(ATTACK_KEYS);
+    input.fire_pressed |= keyboard_input.any_just_pressed(FIRE_KEYS);
+    input.down_held =
+        keyboard_input.pressed(KeyCode::KeyS) || keyboard_input.pressed(KeyCode::ArrowDown);
+}
+
+fn handle_input(
+    mut input: ResMut<PlayerInput>,
+    mut player_query: Query<(&mut Player, &Contacts, &Sprite, &ActivePowerUps)>,
+    one_way_query: Query<(), With<OneWay>>,
+    time: Res<Time>,
+) {
+    if let Ok((mut player, contacts, sprite, power_ups)) = player_query.get_single_mut() {
+        let grounded = player.is_grounded;
+        player.jump_grace.tick(time.delta(), grounded);
+        if grounded {
+            player.air_jumps_used = 0;
+            player.jumping = false;
+        }
+
+        player.input_lock.tick(time.delta());
+        player.dash.tick(time.delta());
+        player.dash_cooldown.tick(time.delta());
+
+        if std::mem::take(&mut input.dash_pressed) && player.dash_cooldown.finished() {
+            // Dash the way the player is heading, or facing if they're standing still
+            let direction = if input.direction != 0.0 {
+                input.direction
+            } else if sprite.flip_x {
+                -1.0
+            } else {
+                1.0
+            };
+            player.velocity = Vec2::new(direction * DASH_SPEED, 0.0);
+            player.dash = Timer::from_seconds(DASH_SECONDS, TimerMode::Once);
+            player.dash_cooldown = Timer::from_seconds(DASH_COOLDOWN_SECONDS, TimerMode::Once);
+        }
+        let jump_pressed = std::mem::take(&mut input.jump_pressed);
+        if player.is_dashing() {
+            // Buffer a jump pressed mid-dash like any other, so it only fires
+            // after the dash if it was pressed close enough to the end
+            if jump_pressed {
+                player.jump_grace.press();
+            }
+            return;
+        }
+
+        let crawling_too_fast = player.crouching && player.velocity.x.abs() >= PLAYER_CRAWL_SPEED;
+        if player.input_lock.finished() && !crawling_too_fast {
+            let acceleration = PLAYER_ACCELERATION * speed_scale(power_ups);
+            player.velocity.x += input.direction * acceleration * time.delta_seconds();
+        }
 
-        if keyboard_input.just_pressed(KeyCode::KeyW)
-            || keyboard_input.just_pressed(KeyCode::ArrowUp)
-            || keyboard_input.just_pressed(KeyCode::Space)
-        {
-            player.jump_buffer.reset();
+        let on_one_way = player
+            .ground
+            .is_some_and(|ground| one_way_query.contains(ground));
+
+        if jump_pressed && input.down_held && on_one_way {
+            // Down + jump drops through a one-way platform instead of jumping
+            player.drop_through = player.ground;
+            player.ground = None;
+            player.is_grounded = false;
+            player.jump_grace.consume();
+        } else if jump_pressed {
+            player.jump_grace.press();
         }
 
-        if !player.jump_buffer.finished() && !player.coyote_time.finished() {
+        let wall_normal = contacts.wall_normal().filter(|_| !grounded);
+
+        if player.jump_grace.try_jump() {
             player.velocity.y = PLAYER_JUMP_STRENGTH;
             player.is_grounded = false;
-            player.jump_buffer.finished();
-            player.coyote_time.finished();
+            player.jumping = true;
+        } else if let Some(normal) = wall_normal.filter(|_| player.jump_grace.take_press()) {
+            // Kick off the wall, away from it
+            player.velocity = Vec2::new(normal * PLAYER_SPEED, PLAYER_JUMP_STRENGTH);
+            player.input_lock = Timer::from_seconds(WALL_JUMP_LOCK_SECONDS, TimerMode::Once);
+            player.jumping = true;
+        } else if player.air_jumps_used < player.air_jumps && player.jump_grace.take_press() {
+            player.velocity.y = PLAYER_JUMP_STRENGTH;
+            player.air_jumps_used += 1;
+            player.jumping = true;
         }
     }
 }
 
+/// Holding down on the ground crouches into a `PLAYER_CROUCH_SIZE` box that
+/// fits under overhead obstacles. Standing back up waits until there's room.
+fn update_crouch(
+    input: Res<PlayerInput>,
+    mut player_query: Query<(&mut Transform, &mut Player, &mut BodySize, &mut Sprite)>,
+    platform_query: Query<&Transform, (With<Platform>, Without<Player>)>,
+) {
+    if let Ok((mut transform, mut player, mut size, mut sprite)) = player_query.get_single_mut() {
+        let crouch = input.down_held && player.is_grounded;
+        if crouch == player.crouching {
+            return;
+        }
+
+        // Keep the feet where they are and grow or shrink from the top
+        let new_size = if crouch {
+            PLAYER_CROUCH_SIZE
+        } else {
+            PLAYER_SIZE
+        };
+        let feet = transform.translation.y - size.y / 2.0;
+        let center_y = feet + new_size.y / 2.0;
+        if !crouch {
+            let standing = Aabb::new(
+                Vec2::new(transform.translation.x, center_y + GROUND_TOLERANCE),
+                new_size,
+            );
+            let blocked = platform_query
+                .iter()
+                .any(|platform| standing.overlaps(&Aabb::from_transform(platform)));
+            if blocked {
+                return;
+            }
+        }
+
+        transform.translation.y = center_y;
+        size.0 = new_size;
+        player.crouching = crouch;
+
+        // Move the sprite up by as much as the body moved down, so it stays on the floor
+        let offset = (PLAYER_SIZE.y - new_size.y) / 2.0 / PLAYER_SPRITE_HEIGHT;
+        sprite.anchor = Anchor::Custom(Vec2::new(0.0, -offset));
+    }
+}
+
 fn zero_player_velocity_on_pause(mut query: Query<&mut Player>) {
     if let Ok(mut player) = query.get_single_mut() {
         player.velocity = Vec2::ZERO;
     }
 }
 
-fn apply_forces(mut player_query: Query<&mut Player>, time: Res<Time>) {
-    if let Ok(mut player) = player_query.get_single_mut() {
+fn apply_forces(
+    input: Res<PlayerInput>,
+    mut player_query: Query<(&mut Player, &Contacts, &ActivePowerUps)>,
+    time: Res<Time>,
+) {
+    if let Ok((mut player, contacts, power_ups)) = player_query.get_single_mut() {
+        // A dash holds its speed and height until it's over
+        if player.is_dashing() {
+            return;
+        }
         if !player.is_grounded {
-            player.velocity.y -= GRAVITY * time.delta_seconds();
+            let gravity = if player.velocity.y > 0.0 {
+                GRAVITY
+            } else {
+                PLAYER_FALL_GRAVITY
+            };
+            player.velocity.y -= gravity * time.delta_seconds();
+        }
+        if is_wall_sliding(&player, contacts, &input) {
+            player.velocity.y = player.velocity.y.max(-WALL_SLIDE_SPEED);
+        }
+        // Letting go of jump early cuts the jump short
+        if player.jumping && !input.jump_held {
+            player.velocity.y = player.velocity.y.min(PLAYER_JUMP_CUT_SPEED);
         }
         // Apply damping to the horizontal velocity
-        player.velocity.x *= PLAYER_DAMPING;
+        player.velocity.x *= if player.crouching {
+            PLAYER_SLIDE_DAMPING
+        } else {
+            PLAYER_DAMPING
+        };
         // Clamp the velocity to the maximum speed
-        player.velocity.x = player.velocity.x.clamp(-PLAYER_SPEED, PLAYER_SPEED);
+        let max_speed = PLAYER_SPEED * speed_scale(power_ups);
+        player.velocity.x = player.velocity.x.clamp(-max_speed, max_speed);
+    }
+}
+
+fn speed_scale(power_ups: &ActivePowerUps) -> f32 {
+    if power_ups.is_active(PowerUp::SpeedBoost) {
+        SPEED_BOOST_SCALE
+    } else {
+        1.0
     }
 }
 
-fn apply_velocity(mut query: Query<(&mut Transform, &Player)>, time: Res<Time>) {
-    for (mut transform, player) in &mut query {
-        transform.translation.x += player.velocity.x * time.delta_seconds();
-        transform.translation.y += player.velocity.y * time.delta_seconds();
+/// Falling while pressing into a wall slides slowly down it.
+fn is_wall_sliding(player: &Player, contacts: &Contacts, input: &PlayerInput) -> bool {
+    !player.is_grounded
+        && player.velocity.y <= 0.0
+        && contacts
+            .wall_normal()
+            .is_some_and(|normal| input.direction * normal < 0.0)
+}
+
+/// Moves the player along with the platform they're standing on.
+fn ride_platform(
+    mut player_query: Query<(&mut Transform, &Player)>,
+    platform_query: Query<&MovingPlatform>,
+) {
+    if let Ok((mut transform, player)) = player_query.get_single_mut() {
+        if let Some(moving) = player.ground.and_then(|ground| platform_query.get(ground).ok()) {
+            transform.translation += moving.delta.extend(0.0);
+        }
     }
 }
 
+/// Moves the player by its velocity, stopping at any platforms in the way.
+#[cfg(not(feature = "rapier"))]
 fn check_collisions(
-    mut player_query: Query<(&mut Transform, &mut Player)>,
-    platform_query: Query<&Transform, (With<Platform>, Without<Player>)>,
+    mut player_query: Query<(&mut Transform, &mut Player, &mut Contacts, &BodySize)>,
+    platform_query: Query<(Entity, &Transform, Has<OneWay>), (With<Platform>, Without<Player>)>,
+    time: Res<Time>,
 ) {
-    if let Ok((mut player_transform, mut player)) = player_query.get_single_mut() {
-        let was_grounded = player.is_grounded;
-        player.is_grounded = false;
-
-        let player_size = PLAYER_SIZE;
-        let player_pos = player_transform.translation;
-
-        for platform_transform in &platform_query {
-            let platform_size = Vec2::new(platform_transform.scale.x, platform_transform.scale.y);
-            let platform_pos = platform_transform.translation;
-
-            let x_collision = (player_pos.x - player_size.x / 2.0)
-                < (platform_pos.x + platform_size.x / 2.0)
-                && (player_pos.x + player_size.x / 2.0) > (platform_pos.x - platform_size.x / 2.0);
-            let y_collision = (player_pos.y - player_size.y / 2.0)
-                < (platform_pos.y + platform_size.y / 2.0)
-                && (player_pos.y + player_size.y / 2.0) > (platform_pos.y - platform_size.y / 2.0);
-
-            if x_collision && y_collision && player.velocity.y <= 0.0 {
-                let penetration =
-                    (platform_pos.y + platform_size.y / 2.0) - (player_pos.y - player_size.y / 2.0);
-                if penetration > 0.0 {
-                    player_transform.translation.y += penetration;
-                    player.velocity.y = 0.0;
-                    player.is_grounded = true;
-                }
-            }
+    if let Ok((mut player_transform, mut player, mut contacts, size)) =
+        player_query.get_single_mut()
+    {
+        let player = &mut *player;
+        player.ground = collision::move_body(
+            &mut player_transform,
+            **size,
+            &mut player.velocity,
+            time.delta_seconds(),
+            &platform_query,
+            &mut player.drop_through,
+            &mut contacts,
+        );
+        player.is_grounded = player.ground.is_some();
+    }
+}
+
+#[cfg(not(feature = "rapier"))]
+fn check_hazards(
+    mut player_query: Query<(&Transform, &mut Player, &BodySize, &ActivePowerUps)>,
+    hazard_query: Query<(&Transform, &Hazard), Without<Player>>,
+    time: Res<Time>,
+) {
+    if let Ok((player_transform, mut player, size, power_ups)) = player_query.get_single_mut() {
+        if power_ups.is_active(PowerUp::Shield) {
+            return;
         }
+        let player_box = Aabb::new(player_transform.translation.truncate(), **size);
 
-        // If we just landed, reset the coyote time timer
-        if player.is_grounded && !was_grounded {
-            player.coyote_time.reset();
+        for (hazard_transform, hazard) in &hazard_query {
+            if player_box.overlaps(&Aabb::from_transform(hazard_transform)) {
+                player.health -= hazard.damage_per_second * time.delta_seconds();
+            }
         }
     }
 }
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::finished_timer;
+
+/// A timed effect. As a component it marks a pickup that grants it.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerUp {
+    /// Zombies and hazards can't hurt the player.
+    Shield,
+    /// Slows the whole game down.
+    SlowMo,
+    /// Pulls in coins from much further away.
+    Magnet,
+    /// Raises the player's top speed.
+    SpeedBoost,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 4] = [
+        PowerUp::Shield,
+        PowerUp::SlowMo,
+        PowerUp::Magnet,
+        PowerUp::SpeedBoost,
+    ];
+
+    /// How long the effect lasts after pickup, in real seconds.
+    pub fn seconds(self) -> f32 {
+        match self {
+            PowerUp::Shield => 8.0,
+            PowerUp::SlowMo => 5.0,
+            PowerUp::Magnet => 10.0,
+            PowerUp::SpeedBoost => 6.0,
+        }
+    }
+
+    /// Pickups and their HUD icons share a colour per power-up.
+    pub fn color(self) -> Color {
+        match self {
+            PowerUp::Shield => Color::rgb(0.3, 0.6, 1.0),
+            PowerUp::SlowMo => Color::rgb(0.7, 0.4, 1.0),
+            PowerUp::Magnet => Color::rgb(1.0, 0.5, 0.1),
+            PowerUp::SpeedBoost => Color::rgb(0.4, 1.0, 0.3),
+        }
+    }
+}
+
+/// The power-ups the player has running, each with its own timer.
+#[derive(Component, Clone, Debug)]
+pub struct ActivePowerUps {
+    shield: Timer,
+    slow_mo: Timer,
+    magnet: Timer,
+    speed_boost: Timer,
+}
+
+impl Default for ActivePowerUps {
+    fn default() -> Self {
+        Self {
+            shield: finished_timer(),
+            slow_mo: finished_timer(),
+            magnet: finished_timer(),
+            speed_boost: finished_timer(),
+        }
+    }
+}
+
+impl ActivePowerUps {
+    fn timer(&self, power_up: PowerUp) -> &Timer {
+        match power_up {
+            PowerUp::Shield => &self.shield,
+            PowerUp::SlowMo => &self.slow_mo,
+            PowerUp::Magnet => &self.magnet,
+            PowerUp::SpeedBoost => &self.speed_boost,
+        }
+    }
+
+    fn timer_mut(&mut self, power_up: PowerUp) -> &mut Timer {
+        match power_up {
+            PowerUp::Shield => &mut self.shield,
+            PowerUp::SlowMo => &mut self.slow_mo,
+            PowerUp::Magnet => &mut self.magnet,
+            PowerUp::SpeedBoost => &mut self.speed_boost,
+        }
+    }
+
+    /// Starts `power_up` for its full duration. Picking up one that is
+    /// already running starts it over rather than stacking.
+    pub fn activate(&mut self, power_up: PowerUp) {
+        *self.timer_mut(power_up) = Timer::from_seconds(power_up.seconds(), TimerMode::Once);
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        !self.timer(power_up).finished()
+    }
+
+    /// Seconds left on `power_up`, zero once it has run out.
+    pub fn remaining_secs(&self, power_up: PowerUp) -> f32 {
+        self.timer(power_up).remaining_secs()
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        for power_up in PowerUp::ALL {
+            self.timer_mut(power_up).tick(delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_is_active_at_first() {
+        let active = ActivePowerUps::default();
+        for power_up in PowerUp::ALL {
+            assert!(!active.is_active(power_up));
+            assert_eq!(active.remaining_secs(power_up), 0.0);
+        }
+    }
+
+    #[test]
+    fn runs_out_after_its_duration() {
+        let mut active = ActivePowerUps::default();
+        active.activate(PowerUp::Shield);
+        active.tick(Duration::from_secs_f32(PowerUp::Shield.seconds() - 1.0));
+        assert!(active.is_active(PowerUp::Shield));
+        assert!(!active.is_active(PowerUp::Magnet));
+        assert!((active.remaining_secs(PowerUp::Shield) - 1.0).abs() < 1e-3);
+
+        active.tick(Duration::from_secs(1));
+        assert!(!active.is_active(PowerUp::Shield));
+    }
+
+    #[test]
+    fn picking_up_again_starts_over() {
+        let mut active = ActivePowerUps::default();
+        active.activate(PowerUp::SpeedBoost);
+        active.tick(Duration::from_secs(3));
+        active.activate(PowerUp::SpeedBoost);
+        assert_eq!(
+            active.remaining_secs(PowerUp::SpeedBoost),
+            PowerUp::SpeedBoost.seconds()
+        );
+    }
+}
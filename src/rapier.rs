@@ -0,0 +1,366 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{
+    ActiveCollisionTypes, ActiveEvents, CharacterLength, Collider, ColliderScale, CollisionEvent,
+    MoveShapeOptions, NoUserData, QueryFilter, RapierContext, RapierPhysicsPlugin, RigidBody,
+    Sensor,
+};
+use fuzzy_runner::collision::{is_stomp, Aabb, Contact, ContactHits, Contacts, Resolution};
+use fuzzy_runner::powerup::{ActivePowerUps, PowerUp};
+use fuzzy_runner::{
+    BodySize, CrumblingPlatform, Dying, Enemy, Hazard, MovingPlatform, OneWay, Platform, Player,
+    PlayerHit, GROUND_TOLERANCE,
+};
+
+use crate::combat::stomp_zombie;
+
+const PIXELS_PER_METER: f32 = 100.0;
+/// Gap the character controller keeps between a body and what it touches.
+const SKIN: f32 = 0.1;
+
+/// Physics backend built on Rapier, enabled with the `rapier` cargo feature.
+///
+/// Bodies are moved with Rapier's kinematic character controller instead of
+/// the hand-rolled `collision` module, and hazard and zombie damage comes from
+/// Rapier's collision events. Gameplay rules like one-way platforms and
+/// dropping through them stay the same.
+pub struct RapierBackendPlugin;
+
+impl Plugin for RapierBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PIXELS_PER_METER)
+                .in_fixed_schedule(),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                attach_platform_colliders,
+                detach_platform_colliders,
+                attach_hazard_colliders,
+                attach_body_colliders,
+                resize_body_colliders,
+                track_touching,
+            ),
+        );
+    }
+}
+
+/// Entities with a hazard or zombie collider currently touching the player.
+#[derive(Component, Default)]
+pub struct Touching(Vec<Entity>);
+
+/// Platforms are sized by their transform's scale, which Rapier applies to
+/// colliders, so they all share a unit box.
+fn attach_platform_colliders(
+    mut commands: Commands,
+    query: Query<(Entity, Has<MovingPlatform>, Has<CrumblingPlatform>), Added<Platform>>,
+) {
+    for (entity, moving, crumbling) in &query {
+        let body = if moving || crumbling {
+            RigidBody::KinematicPositionBased
+        } else {
+            RigidBody::Fixed
+        };
+        commands
+            .entity(entity)
+            .insert((body, Collider::cuboid(0.5, 0.5)));
+    }
+}
+
+/// Crumbling platforms stop being `Platform`s while they fall away.
+fn detach_platform_colliders(mut commands: Commands, mut removed: RemovedComponents<Platform>) {
+    for entity in removed.read() {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.remove::<Collider>();
+        }
+    }
+}
+
+fn attach_hazard_colliders(mut commands: Commands, query: Query<Entity, Added<Hazard>>) {
+    for entity in &query {
+        commands
+            .entity(entity)
+            .insert((RigidBody::Fixed, Collider::cuboid(0.5, 0.5), Sensor));
+    }
+}
+
+/// The player and zombie sprites are scaled down, so their colliders ignore
+/// the transform's scale.
+fn attach_body_colliders(
+    mut commands: Commands,
+    body_query: Query<(Entity, &BodySize, Has<Player>), Added<BodySize>>,
+) {
+    for (entity, size, is_player) in &body_query {
+        let mut body = commands.entity(entity);
+        body.insert((
+            RigidBody::KinematicPositionBased,
+            Collider::cuboid(size.x / 2.0, size.y / 2.0),
+            ColliderScale::Absolute(Vec2::ONE),
+            ActiveEvents::COLLISION_EVENTS,
+            ActiveCollisionTypes::default()
+                | ActiveCollisionTypes::KINEMATIC_STATIC
+                | ActiveCollisionTypes::KINEMATIC_KINEMATIC,
+        ));
+        if is_player {
+            body.insert(Touching::default());
+        }
+    }
+}
+
+/// Keeps body colliders matching their `BodySize`, e.g. while the player crouches.
+fn resize_body_colliders(mut query: Query<(&BodySize, &mut Collider), Changed<BodySize>>) {
+    for (size, mut collider) in &mut query {
+        *collider = Collider::cuboid(size.x / 2.0, size.y / 2.0);
+    }
+}
+
+/// Keeps the player's `Touching` list up to date from Rapier's collision events.
+fn track_touching(
+    mut events: EventReader<CollisionEvent>,
+    mut player_query: Query<(Entity, &mut Touching)>,
+    damaging_query: Query<(), Or<(With<Hazard>, With<Enemy>)>>,
+) {
+    let Ok((player, mut touching)) = player_query.get_single_mut() else {
+        events.clear();
+        return;
+    };
+
+    for event in events.read() {
+        let (a, b, started) = match *event {
+            CollisionEvent::Started(a, b, _) => (a, b, true),
+            CollisionEvent::Stopped(a, b, _) => (a, b, false),
+        };
+        let other = match (a == player, b == player) {
+            (true, _) => b,
+            (_, true) => a,
+            _ => continue,
+        };
+        if !damaging_query.contains(other) {
+            continue;
+        }
+
+        touching.0.retain(|&entity| entity != other);
+        if started {
+            touching.0.push(other);
+        }
+    }
+}
+
+/// Moves a body with Rapier's character controller, stopping at platforms
+/// the same way `collision::move_and_collide` does.
+fn move_body(
+    context: &mut RapierContext,
+    translation: &mut Vec3,
+    velocity: &mut Vec2,
+    size: Vec2,
+    delta_seconds: f32,
+    platform_query: &Query<
+        (&Transform, Has<OneWay>),
+        (With<Platform>, Without<Player>, Without<Enemy>),
+    >,
+    drop_through: Option<Entity>,
+) -> Resolution {
+    let shape = Collider::cuboid(size.x / 2.0, size.y / 2.0);
+    let feet = translation.y - size.y / 2.0;
+    let falling = velocity.y <= 0.0;
+
+    // Only platforms block, and one-way platforms only catch feet coming down onto them
+    let blocks = |entity: Entity| {
+        if drop_through == Some(entity) {
+            return false;
+        }
+        platform_query
+            .get(entity)
+            .is_ok_and(|(transform, one_way)| {
+                let top = transform.translation.y + transform.scale.y / 2.0;
+                !one_way || (falling && feet >= top - GROUND_TOLERANCE)
+            })
+    };
+    let filter = QueryFilter::new().exclude_sensors().predicate(&blocks);
+    let options = MoveShapeOptions {
+        offset: CharacterLength::Absolute(SKIN),
+        autostep: None,
+        snap_to_ground: None,
+        apply_impulse_to_dynamic_bodies: false,
+        ..default()
+    };
+
+    let mut contacts = Contacts::default();
+    let output = context.move_shape(
+        *velocity * delta_seconds,
+        &shape,
+        translation.truncate(),
+        0.0,
+        0.0,
+        &options,
+        filter,
+        |collision| {
+            if let Some(details) = collision.toi.details {
+                contacts.0.push(Contact {
+                    entity: collision.entity,
+                    normal: axis_normal(details.normal2),
+                });
+            }
+        },
+    );
+    translation.x += output.effective_translation.x;
+    translation.y += output.effective_translation.y;
+
+    // A body at rest doesn't move into its floor, so look for it just below
+    if falling && !contacts.is_grounded() {
+        let ground = context.cast_shape(
+            translation.truncate(),
+            0.0,
+            Vec2::NEG_Y,
+            &shape,
+            GROUND_TOLERANCE,
+            true,
+            filter,
+        );
+        if let Some((entity, _)) = ground {
+            contacts.0.push(Contact {
+                entity,
+                normal: Vec2::Y,
+            });
+        }
+    }
+
+    // Stop moving into the surfaces that were hit
+    for contact in &contacts.0 {
+        if velocity.dot(contact.normal) < 0.0 {
+            *velocity -= contact.normal * velocity.dot(contact.normal);
+        }
+    }
+
+    let body = Aabb::new(
+        translation.truncate() - Vec2::new(0.0, GROUND_TOLERANCE / 2.0),
+        size + Vec2::new(0.0, GROUND_TOLERANCE),
+    );
+    let overlapping_ignored = drop_through
+        .and_then(|entity| platform_query.get(entity).ok())
+        .is_some_and(|(transform, _)| body.overlaps(&Aabb::from_transform(transform)));
+
+    Resolution {
+        contacts,
+        overlapping_ignored,
+    }
+}
+
+/// Snaps a hit normal to the nearest axis, since every platform is axis-aligned.
+fn axis_normal(normal: Vec2) -> Vec2 {
+    if normal.y.abs() >= normal.x.abs() {
+        Vec2::new(0.0, normal.y.signum())
+    } else {
+        Vec2::new(normal.x.signum(), 0.0)
+    }
+}
+
+/// Moves the player by its velocity, stopping at any platforms in the way.
+pub fn check_collisions(
+    mut player_query: Query<(&mut Transform, &mut Player, &mut Contacts, &BodySize)>,
+    platform_query: Query<
+        (&Transform, Has<OneWay>),
+        (With<Platform>, Without<Player>, Without<Enemy>),
+    >,
+    mut context: ResMut<RapierContext>,
+    time: Res<Time>,
+) {
+    if let Ok((mut player_transform, mut player, mut contacts, size)) =
+        player_query.get_single_mut()
+    {
+        let drop_through = player.drop_through;
+        let resolution = move_body(
+            &mut context,
+            &mut player_transform.translation,
+            &mut player.velocity,
+            **size,
+            time.delta_seconds(),
+            &platform_query,
+            drop_through,
+        );
+
+        player.ground = resolution.settle(&mut contacts, &mut player.drop_through);
+        player.is_grounded = player.ground.is_some();
+    }
+}
+
+pub fn check_hazards(
+    mut player_query: Query<(&mut Player, &ActivePowerUps, &Touching)>,
+    hazard_query: Query<&Hazard>,
+    time: Res<Time>,
+) {
+    if let Ok((mut player, power_ups, touching)) = player_query.get_single_mut() {
+        if power_ups.is_active(PowerUp::Shield) {
+            return;
+        }
+        for hazard in hazard_query.iter_many(&touching.0) {
+            player.health -= hazard.damage_per_second * time.delta_seconds();
+        }
+    }
+}
+
+/// Moves zombies by their velocity, landing them on floors and stopping them at walls and ceilings.
+pub fn zombie_platform_collision(
+    mut zombie_query: Query<
+        (&mut Transform, &mut Enemy, &mut Contacts, &BodySize),
+        Without<Platform>,
+    >,
+    platform_query: Query<
+        (&Transform, Has<OneWay>),
+        (With<Platform>, Without<Player>, Without<Enemy>),
+    >,
+    mut context: ResMut<RapierContext>,
+    time: Res<Time>,
+) {
+    for (mut zombie_transform, mut zombie, mut contacts, size) in zombie_query.iter_mut() {
+        let drop_through = zombie.drop_through;
+        let resolution = move_body(
+            &mut context,
+            &mut zombie_transform.translation,
+            &mut zombie.velocity,
+            **size,
+            time.delta_seconds(),
+            &platform_query,
+            drop_through,
+        );
+
+        zombie.ground = resolution.settle(&mut contacts, &mut zombie.drop_through);
+        zombie.is_grounded = zombie.ground.is_some();
+    }
+}
+
+/// Landing on a zombie's head kills it; touching it any other way hurts.
+pub fn zombie_player_collision(
+    mut commands: Commands,
+    mut hits: EventWriter<PlayerHit>,
+    mut player_query: Query<(&Transform, &mut Player, &BodySize, &ActivePowerUps, &Touching)>,
+    mut zombie_query: Query<(&Transform, &mut Enemy, &BodySize), (Without<Player>, Without<Dying>)>,
+) {
+    if let Ok((player_transform, mut player, player_size, power_ups, touching)) =
+        player_query.get_single_mut()
+    {
+        // Dashing through zombies is safe
+        if player.is_dashing() {
+            return;
+        }
+        let player_box = Aabb::new(player_transform.translation.truncate(), **player_size);
+        let mut contact_hits = ContactHits::default();
+        for &entity in &touching.0 {
+            let Ok((zombie_transform, mut zombie, zombie_size)) = zombie_query.get_mut(entity)
+            else {
+                continue;
+            };
+            let zombie_box = Aabb::new(zombie_transform.translation.truncate(), **zombie_size);
+            if is_stomp(&player_box, player.velocity, &zombie_box, zombie.velocity) {
+                stomp_zombie(&mut commands, entity, &mut zombie, &mut player);
+                contact_hits.stomp();
+            } else {
+                contact_hits.touch(zombie_box.center, zombie.damage);
+            }
+        }
+
+        if let Some(hit) = contact_hits.hit(power_ups.is_active(PowerUp::Shield)) {
+            hits.send(hit);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+use std::path::Path;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState};
+use bevy::prelude::*;
+use bevy::utils::{get_short_name, BoxedFuture};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// An asset read straight from a RON file, or a JSON one if its extension
+/// is listed.
+pub trait RonAsset: Asset + DeserializeOwned {
+    /// Extensions the asset's files end in, e.g. `curve.ron`.
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Tidies the asset up after parsing.
+    fn finish(&mut self) {}
+
+    /// Checks for mistakes parsing can't catch, describing the first found.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Loads any `RonAsset`.
+pub struct RonAssetLoader<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for RonAssetLoader<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RonAssetLoaderError {
+    #[error("could not read asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse RON asset: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not parse JSON asset: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid asset: {0}")]
+    Invalid(String),
+}
+
+/// Parses the file at `path` as JSON if it ends in `.json` and as RON
+/// otherwise.
+pub fn parse<T: RonAsset>(path: &Path, bytes: &[u8]) -> Result<T, RonAssetLoaderError> {
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension == "json");
+    let mut asset: T = if is_json {
+        serde_json::from_slice(bytes)?
+    } else {
+        ron::de::from_bytes(bytes)?
+    };
+    asset.finish();
+    asset.validate().map_err(RonAssetLoaderError::Invalid)?;
+    Ok(asset)
+}
+
+impl<T: RonAsset> AssetLoader for RonAssetLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = RonAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            parse(load_context.path(), &bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
+
+/// Handle to the asset the `T` resource is a copy of.
+#[derive(Resource)]
+pub struct ResourceHandle<T: Asset>(pub Handle<T>);
+
+/// Keeps the `T` resource in step with the asset behind its
+/// `ResourceHandle`, including when the file is edited while the game is
+/// running. If the asset fails to load the resource falls back to
+/// `T::default()`.
+pub fn sync_asset_resource<T: RonAsset + Resource + Clone + Default>(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<T>>,
+    asset_server: Res<AssetServer>,
+    handle: Res<ResourceHandle<T>>,
+    assets: Res<Assets<T>>,
+    current: Option<Res<T>>,
+) {
+    for event in events.read() {
+        if event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0) {
+            if let Some(asset) = assets.get(&handle.0) {
+                commands.insert_resource(asset.clone());
+            }
+        }
+    }
+
+    if current.is_none() && asset_server.load_state(&handle.0) == LoadState::Failed {
+        let name = get_short_name(std::any::type_name::<T>());
+        warn!("Could not load the {name} asset, falling back to the defaults");
+        commands.insert_resource(T::default());
+    }
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Where the save file lives, relative to the working directory.
+pub const SAVE_PATH: &str = "save.ron";
+
+/// Progress kept between runs.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SaveData {
+    /// Coins banked at the end of every run so far.
+    #[serde(default)]
+    pub banked_coins: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse save file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write save file: {0}")]
+    Write(#[from] ron::Error),
+}
+
+impl SaveData {
+    /// Reads the save at `path`. A missing file is a fresh save.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(ron::from_str(&text)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn store(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("fuzzy_runner_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn missing_file_is_a_fresh_save() {
+        let save = SaveData::load(temp_path("missing.ron")).unwrap();
+        assert_eq!(save, SaveData::default());
+    }
+
+    #[test]
+    fn round_trips() {
+        let path = temp_path("round_trip.ron");
+        let save = SaveData { banked_coins: 42 };
+        save.store(&path).unwrap();
+        let loaded = SaveData::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), save);
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        let path = temp_path("garbage.ron");
+        fs::write(&path, "not a save").unwrap();
+        let loaded = SaveData::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(SaveError::Parse(_))));
+    }
+}
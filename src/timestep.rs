@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+use fuzzy_runner::{Interpolated, Interpolation, FIXED_TIMESTEP_HZ};
+
+/// Runs gameplay in `FixedUpdate` at `FIXED_TIMESTEP_HZ` and interpolates
+/// sprites between ticks.
+pub struct TimestepPlugin;
+
+impl Plugin for TimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+            .add_systems(FixedFirst, restore_gameplay_translation)
+            .add_systems(FixedLast, record_gameplay_translation)
+            .add_systems(Update, interpolate_translation.in_set(Interpolation));
+    }
+}
+
+/// Puts entities back where gameplay left them before the next tick runs.
+fn restore_gameplay_translation(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in &mut query {
+        transform.translation = interpolated.current;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+fn record_gameplay_translation(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in &mut query {
+        interpolated.current = transform.translation;
+    }
+}
+
+fn interpolate_translation(
+    mut query: Query<(&mut Transform, &Interpolated)>,
+    time: Res<Time<Fixed>>,
+) {
+    let alpha = time.overstep_fraction();
+    for (mut transform, interpolated) in &mut query {
+        transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
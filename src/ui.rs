@@ -1,9 +1,16 @@
 use bevy::prelude::*;
+use fuzzy_runner::powerup::{ActivePowerUps, PowerUp};
+use fuzzy_runner::save::SaveData;
 use fuzzy_runner::{
-    despawn_screen, Distance, DistanceText, GameConfig, GameState, HealthBar, OnGameScreen,
-    OnPauseMenu, OnSettingsMenu, Player,
+    despawn_screen, AmmoText, CoinText, Coins, DashCooldownBar, Distance, DistanceText,
+    GameConfig, GameState, HealthBar, OnGameScreen, OnPauseMenu, OnSettingsMenu, Player, RunSeed,
+    MAX_AIR_JUMPS,
 };
 
+const DASH_READY_COLOR: Color = Color::CYAN;
+/// Biomes the settings menu cycles through, as tagged on the bundled chunks.
+const BIOMES: [&str; 2] = ["rooftop", "neon"];
+
 #[derive(Resource)]
 struct GameOverTimer(Timer);
 
@@ -18,12 +25,37 @@ enum MenuButtonAction {
 enum SettingsButtonAction {
     IncrementEnemies,
     DecrementEnemies,
+    IncrementAirJumps,
+    DecrementAirJumps,
+    ToggleRegeneration,
+    CycleBiome,
+    RandomSeed,
     Back,
 }
 
 #[derive(Component)]
 struct EnemyCountText;
 
+#[derive(Component)]
+struct AirJumpsText;
+
+#[derive(Component)]
+struct RegenerationText;
+
+#[derive(Component)]
+struct BiomeText;
+
+#[derive(Component)]
+struct SeedText;
+
+/// HUD icon shown while a power-up is running.
+#[derive(Component)]
+struct PowerUpIcon(PowerUp);
+
+/// Seconds left on a power-up, inside its icon.
+#[derive(Component)]
+struct PowerUpTimeText(PowerUp);
+
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
@@ -31,7 +63,10 @@ impl Plugin for UiPlugin {
         app.add_systems(OnEnter(GameState::Playing), setup_game_ui)
             .add_systems(OnEnter(GameState::Paused), setup_pause_menu)
             .add_systems(OnExit(GameState::Paused), despawn_screen::<OnPauseMenu>)
-            .add_systems(OnEnter(GameState::GameOver), setup_game_over_screen)
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                setup_game_over_screen.after(crate::coins::bank_coins),
+            )
             .add_systems(OnEnter(GameState::SettingsMenu), setup_settings_menu)
             .add_systems(OnExit(GameState::GameOver), despawn_screen::<OnPauseMenu>)
             .add_systems(
@@ -45,9 +80,19 @@ impl Plugin for UiPlugin {
                     handle_settings_menu_actions.run_if(in_state(GameState::SettingsMenu)),
                     handle_menu_button_actions.run_if(in_state(GameState::Paused)),
                     update_enemy_count_text.run_if(in_state(GameState::SettingsMenu)),
+                    update_air_jumps_text.run_if(in_state(GameState::SettingsMenu)),
+                    update_regeneration_text.run_if(in_state(GameState::SettingsMenu)),
+                    update_biome_text.run_if(in_state(GameState::SettingsMenu)),
+                    (handle_seed_input, update_seed_text)
+                        .chain()
+                        .run_if(in_state(GameState::SettingsMenu)),
                     game_over_reset_timer.run_if(in_state(GameState::GameOver)),
                     update_health_bar.run_if(in_state(GameState::Playing)),
+                    update_dash_cooldown_bar.run_if(in_state(GameState::Playing)),
                     update_distance_text.run_if(in_state(GameState::Playing)),
+                    update_ammo_text.run_if(in_state(GameState::Playing)),
+                    update_coin_text.run_if(in_state(GameState::Playing)),
+                    update_power_up_icons.run_if(in_state(GameState::Playing)),
                 ),
             );
     }
@@ -70,6 +115,21 @@ fn handle_settings_menu_actions(
                 SettingsButtonAction::DecrementEnemies => {
                     config.max_enemies = (config.max_enemies - 1).max(1); // Minimum 1
                 }
+                SettingsButtonAction::IncrementAirJumps => {
+                    config.air_jumps = (config.air_jumps + 1).min(MAX_AIR_JUMPS);
+                }
+                SettingsButtonAction::DecrementAirJumps => {
+                    config.air_jumps = config.air_jumps.saturating_sub(1);
+                }
+                SettingsButtonAction::ToggleRegeneration => {
+                    config.regeneration = !config.regeneration;
+                }
+                SettingsButtonAction::CycleBiome => {
+                    config.biome = next_biome(config.biome.as_deref()).map(String::from);
+                }
+                SettingsButtonAction::RandomSeed => {
+                    config.seed = None;
+                }
                 SettingsButtonAction::Back => {
                     next_state.set(GameState::Paused);
                 }
@@ -104,6 +164,109 @@ fn update_enemy_count_text(
     }
 }
 
+fn update_air_jumps_text(config: Res<GameConfig>, mut query: Query<&mut Text, With<AirJumpsText>>) {
+    if config.is_changed() {
+        if let Ok(mut text) = query.get_single_mut() {
+            text.sections[0].value = config.air_jumps.to_string();
+        }
+    }
+}
+
+fn regeneration_label(config: &GameConfig) -> &'static str {
+    if config.regeneration {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+fn update_regeneration_text(
+    config: Res<GameConfig>,
+    mut query: Query<&mut Text, With<RegenerationText>>,
+) {
+    if config.is_changed() {
+        if let Ok(mut text) = query.get_single_mut() {
+            text.sections[0].value = regeneration_label(&config).to_string();
+        }
+    }
+}
+
+/// Steps from any biome through each of `BIOMES` and back to any.
+fn next_biome(current: Option<&str>) -> Option<&'static str> {
+    match current {
+        None => BIOMES.first().copied(),
+        Some(current) => BIOMES
+            .iter()
+            .skip_while(|&&biome| biome != current)
+            .nth(1)
+            .copied(),
+    }
+}
+
+fn biome_label(config: &GameConfig) -> String {
+    match &config.biome {
+        Some(biome) => biome.clone(),
+        None => "any".to_string(),
+    }
+}
+
+fn update_biome_text(config: Res<GameConfig>, mut query: Query<&mut Text, With<BiomeText>>) {
+    if config.is_changed() {
+        if let Ok(mut text) = query.get_single_mut() {
+            text.sections[0].value = biome_label(&config);
+        }
+    }
+}
+
+/// Lets the player type a seed with the number keys; Backspace deletes a digit.
+/// The seed takes effect on the next run.
+fn handle_seed_input(keyboard_input: Res<ButtonInput<KeyCode>>, mut config: ResMut<GameConfig>) {
+    for key in keyboard_input.get_just_pressed() {
+        let digit = match key {
+            KeyCode::Digit0 | KeyCode::Numpad0 => 0,
+            KeyCode::Digit1 | KeyCode::Numpad1 => 1,
+            KeyCode::Digit2 | KeyCode::Numpad2 => 2,
+            KeyCode::Digit3 | KeyCode::Numpad3 => 3,
+            KeyCode::Digit4 | KeyCode::Numpad4 => 4,
+            KeyCode::Digit5 | KeyCode::Numpad5 => 5,
+            KeyCode::Digit6 | KeyCode::Numpad6 => 6,
+            KeyCode::Digit7 | KeyCode::Numpad7 => 7,
+            KeyCode::Digit8 | KeyCode::Numpad8 => 8,
+            KeyCode::Digit9 | KeyCode::Numpad9 => 9,
+            KeyCode::Backspace => {
+                config.seed = config.seed.filter(|seed| *seed >= 10).map(|seed| seed / 10);
+                continue;
+            }
+            _ => continue,
+        };
+
+        config.seed = match config.seed {
+            None => Some(digit),
+            // Ignore digits that would overflow the seed
+            Some(seed) => Some(
+                seed.checked_mul(10)
+                    .and_then(|seed| seed.checked_add(digit))
+                    .unwrap_or(seed),
+            ),
+        };
+    }
+}
+
+fn seed_label(config: &GameConfig) -> String {
+    match config.seed {
+        Some(seed) => seed.to_string(),
+        None => "random".to_string(),
+    }
+}
+
+fn update_seed_text(config: Res<GameConfig>, mut query: Query<&mut Text, With<SeedText>>) {
+    if config.is_changed() {
+        if let Ok(mut text) = query.get_single_mut() {
+            text.sections[0].value = seed_label(&config);
+        }
+    }
+}
+
 fn handle_menu_button_actions(
     interaction_query: Query<
         (&Interaction, &MenuButtonAction),
@@ -208,6 +371,173 @@ fn setup_settings_menu(mut commands: Commands, config: Res<GameConfig>) {
                         });
                 });
 
+            // Air Jumps Editor
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::bottom(Val::Px(20.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Air Jumps: ", text_style.clone()));
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: Color::DARK_GRAY.into(),
+                                ..default()
+                            },
+                            SettingsButtonAction::DecrementAirJumps,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("-", text_style.clone()));
+                        });
+
+                    parent.spawn((
+                        TextBundle::from_section(config.air_jumps.to_string(), text_style.clone()),
+                        AirJumpsText,
+                    ));
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: Color::DARK_GRAY.into(),
+                                ..default()
+                            },
+                            SettingsButtonAction::IncrementAirJumps,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("+", text_style.clone()));
+                        });
+                });
+
+            // Regeneration Toggle
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::bottom(Val::Px(20.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Regeneration: ",
+                        text_style.clone(),
+                    ));
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(100.0),
+                                    ..button_style.clone()
+                                },
+                                background_color: Color::DARK_GRAY.into(),
+                                ..default()
+                            },
+                            SettingsButtonAction::ToggleRegeneration,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    regeneration_label(&config),
+                                    text_style.clone(),
+                                ),
+                                RegenerationText,
+                            ));
+                        });
+                });
+
+            // Biome Selector
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::bottom(Val::Px(20.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Biome: ", text_style.clone()));
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(150.0),
+                                    ..button_style.clone()
+                                },
+                                background_color: Color::DARK_GRAY.into(),
+                                ..default()
+                            },
+                            SettingsButtonAction::CycleBiome,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(biome_label(&config), text_style.clone()),
+                                BiomeText,
+                            ));
+                        });
+                });
+
+            // Seed Editor
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::bottom(Val::Px(20.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Seed: ", text_style.clone()));
+
+                    parent.spawn((
+                        TextBundle::from_section(seed_label(&config), text_style.clone()),
+                        SeedText,
+                    ));
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(150.0),
+                                    ..button_style.clone()
+                                },
+                                background_color: Color::DARK_GRAY.into(),
+                                ..default()
+                            },
+                            SettingsButtonAction::RandomSeed,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("Random", text_style.clone()));
+                        });
+                });
+
+            parent.spawn(
+                TextBundle::from_section(
+                    "Type digits to set a seed. Air jumps, biome and seed apply from the next run",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::GRAY,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+
             // Back Button
             parent
                 .spawn((
@@ -345,6 +675,40 @@ fn setup_game_ui(mut commands: Commands) {
             ));
         });
 
+    // Dash Cooldown, next to the health bar
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(230.0),
+                    top: Val::Px(20.0),
+                    width: Val::Px(60.0),
+                    height: Val::Px(30.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                border_color: BorderColor(Color::WHITE),
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+            OnGameScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: DASH_READY_COLOR.into(),
+                    ..default()
+                },
+                DashCooldownBar,
+            ));
+        });
+
     // Distance Text
     commands.spawn((
         TextBundle::from_section(
@@ -364,6 +728,95 @@ fn setup_game_ui(mut commands: Commands) {
         DistanceText,
         OnGameScreen,
     ));
+
+    // Coin Text, next to the distance
+    commands.spawn((
+        TextBundle::from_section(
+            "Coins: 0",
+            TextStyle {
+                font_size: 32.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(300.0),
+            top: Val::Px(60.0),
+            ..default()
+        }),
+        CoinText,
+        OnGameScreen,
+    ));
+
+    // Ammo Text, under the distance
+    commands.spawn((
+        TextBundle::from_section(
+            "Ammo: 0",
+            TextStyle {
+                font_size: 24.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            top: Val::Px(100.0),
+            ..default()
+        }),
+        AmmoText,
+        OnGameScreen,
+    ));
+
+    // Power-up icons, under the ammo
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(20.0),
+                    top: Val::Px(140.0),
+                    column_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            OnGameScreen,
+        ))
+        .with_children(|parent| {
+            for power_up in PowerUp::ALL {
+                parent
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                display: Display::None,
+                                width: Val::Px(40.0),
+                                height: Val::Px(40.0),
+                                align_items: AlignItems::Center,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: power_up.color().into(),
+                            ..default()
+                        },
+                        PowerUpIcon(power_up),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font_size: 20.0,
+                                    color: Color::BLACK,
+                                    ..default()
+                                },
+                            ),
+                            PowerUpTimeText(power_up),
+                        ));
+                    });
+            }
+        });
 }
 
 fn update_health_bar(
@@ -372,7 +825,24 @@ fn update_health_bar(
 ) {
     if let Ok(player) = player_query.get_single() {
         if let Ok(mut health_bar_style) = health_bar_query.get_single_mut() {
-            health_bar_style.width = Val::Percent(player.health);
+            let fraction = (player.health / player.max_health).clamp(0.0, 1.0);
+            health_bar_style.width = Val::Percent(fraction * 100.0);
+        }
+    }
+}
+
+fn update_dash_cooldown_bar(
+    player_query: Query<&Player>,
+    mut bar_query: Query<(&mut Style, &mut BackgroundColor), With<DashCooldownBar>>,
+) {
+    if let Ok(player) = player_query.get_single() {
+        if let Ok((mut style, mut color)) = bar_query.get_single_mut() {
+            style.width = Val::Percent(player.dash_cooldown.fraction() * 100.0);
+            *color = if player.dash_cooldown.finished() {
+                DASH_READY_COLOR.into()
+            } else {
+                Color::GRAY.into()
+            };
         }
     }
 }
@@ -382,11 +852,53 @@ fn update_distance_text(
     mut distance_text_query: Query<&mut Text, With<DistanceText>>,
 ) {
     if let Ok(mut text) = distance_text_query.get_single_mut() {
-        text.sections[0].value = format!("Distance: {:.0}m", distance.0 / 10.0);
+        text.sections[0].value = format!("Distance: {:.0}m", distance.metres());
     }
 }
 
-fn setup_game_over_screen(mut commands: Commands) {
+fn update_ammo_text(
+    player_query: Query<&Player>,
+    mut ammo_text_query: Query<&mut Text, With<AmmoText>>,
+) {
+    if let Ok(player) = player_query.get_single() {
+        if let Ok(mut text) = ammo_text_query.get_single_mut() {
+            text.sections[0].value = format!("Ammo: {}", player.ammo);
+        }
+    }
+}
+
+fn update_coin_text(coins: Res<Coins>, mut coin_text_query: Query<&mut Text, With<CoinText>>) {
+    if let Ok(mut text) = coin_text_query.get_single_mut() {
+        text.sections[0].value = format!("Coins: {}", coins.0);
+    }
+}
+
+fn update_power_up_icons(
+    player_query: Query<&ActivePowerUps>,
+    mut icon_query: Query<(&PowerUpIcon, &mut Style)>,
+    mut time_text_query: Query<(&PowerUpTimeText, &mut Text)>,
+) {
+    if let Ok(power_ups) = player_query.get_single() {
+        for (icon, mut style) in &mut icon_query {
+            style.display = if power_ups.is_active(icon.0) {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+        for (time_text, mut text) in &mut time_text_query {
+            let seconds = power_ups.remaining_secs(time_text.0).ceil() as u32;
+            text.sections[0].value = seconds.to_string();
+        }
+    }
+}
+
+fn setup_game_over_screen(
+    mut commands: Commands,
+    run_seed: Res<RunSeed>,
+    coins: Res<Coins>,
+    save: Res<SaveData>,
+) {
     commands.insert_resource(GameOverTimer(Timer::from_seconds(2.0, TimerMode::Once)));
 
     let text_style = TextStyle {
@@ -403,6 +915,7 @@ fn setup_game_over_screen(mut commands: Commands) {
                     height: Val::Percent(100.0),
                     align_items: AlignItems::Center,
                     justify_content: JustifyContent::Center,
+                    flex_direction: FlexDirection::Column,
                     ..default()
                 },
                 background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
@@ -412,6 +925,19 @@ fn setup_game_over_screen(mut commands: Commands) {
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section("You Died", text_style));
+            let detail_style = TextStyle {
+                font_size: 32.0,
+                color: Color::WHITE,
+                ..default()
+            };
+            parent.spawn(TextBundle::from_section(
+                format!("Coins: {} (banked: {})", coins.0, save.banked_coins),
+                detail_style.clone(),
+            ));
+            parent.spawn(TextBundle::from_section(
+                format!("Seed: {}", run_seed.0),
+                detail_style,
+            ));
         });
 }
 